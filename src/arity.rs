@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use crate::core_ir::*;
+use crate::location;
+use crate::name::Name;
+use crate::suggest;
+use crate::visit::{self, Visitor};
+use ariadne::Label;
+use ariadne::Report;
+use ariadne::ReportKind;
+use thiserror::Error;
+
+/// Numbers of producers and consumers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arity {
+    pub producers: usize,
+    pub consumers: usize,
+}
+
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} producer{} and {} consumer{}",
+            self.producers,
+            plural(self.producers),
+            self.consumers,
+            plural(self.consumers)
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("`{name}` takes {expected}, but {actual} are given")]
+    Mismatch {
+        name: String,
+        expected: Arity,
+        actual: Arity,
+        location: location::Location,
+        /// Where `name` is defined or declared.
+        definition: location::Location,
+    },
+    #[error("unknown {} `{tag}`", describe(*.kind))]
+    UnknownTag {
+        kind: DeclarationKind,
+        tag: String,
+        location: location::Location,
+        /// A similarly-named tag of the same kind and where it is declared.
+        similar: Option<(String, location::Location)>,
+    },
+}
+
+fn describe(kind: DeclarationKind) -> &'static str {
+    match kind {
+        DeclarationKind::Data => "constructor",
+        DeclarationKind::Codata => "destructor",
+    }
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::Mismatch {
+                name,
+                expected,
+                actual,
+                location,
+                definition,
+            } => Report::build(ReportKind::Error, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message(format!("{} given here", actual)))
+                .with_label(
+                    Label::new(definition)
+                        .with_message(format!("`{}` takes {} here", name, expected)),
+                )
+                .finish(),
+            Error::UnknownTag {
+                location, similar, ..
+            } => {
+                let mut report = Report::build(ReportKind::Error, location.clone())
+                    .with_message(message)
+                    .with_label(Label::new(location).with_message("not declared"));
+                if let Some((tag, declaration)) = similar {
+                    report = report.with_label(
+                        Label::new(declaration)
+                            .with_message(format!("a similar name `{}` is declared here", tag)),
+                    );
+                }
+                report.finish()
+            }
+        }
+    }
+}
+
+/// Checks that every `invoke`, constructor, destructor, pattern and copattern
+/// has as many producers and consumers as its definition or declaration.
+pub struct Checker<'a> {
+    program: &'a Program<Name>,
+    definitions: HashMap<&'a Name, &'a Definition<Name>>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(program: &'a Program<Name>) -> Self {
+        Self {
+            program,
+            definitions: program
+                .definitions
+                .iter()
+                .map(|definition| (&definition.name, definition))
+                .collect(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn check(mut self) -> Vec<Error> {
+        let program = self.program;
+        for definition in &program.definitions {
+            self.statement(&definition.body);
+        }
+        self.errors
+    }
+
+    fn compare(
+        &mut self,
+        name: &str,
+        expected: Arity,
+        actual: Arity,
+        location: &location::Location,
+        definition: &location::Location,
+    ) {
+        if expected != actual {
+            self.errors.push(Error::Mismatch {
+                name: name.to_string(),
+                expected,
+                actual,
+                location: location.clone(),
+                definition: definition.clone(),
+            });
+        }
+    }
+
+    /// Compares a use of `tag` against its constructor or destructor signature.
+    fn tag(
+        &mut self,
+        kind: DeclarationKind,
+        tag: &str,
+        actual: Arity,
+        location: &location::Location,
+    ) {
+        let program = self.program;
+        match program.signature(kind, tag) {
+            Some((_, signature)) => {
+                let expected = Arity {
+                    producers: signature.parameters.len(),
+                    consumers: signature.returns.len(),
+                };
+                self.compare(tag, expected, actual, location, &signature.location);
+            }
+            None => {
+                let similar = suggest::closest(
+                    tag,
+                    program
+                        .declarations
+                        .iter()
+                        .filter(|declaration| declaration.kind == kind)
+                        .flat_map(|declaration| &declaration.signatures)
+                        .map(|signature| {
+                            (
+                                signature.tag.as_str(),
+                                (signature.tag.clone(), signature.location.clone()),
+                            )
+                        }),
+                );
+                self.errors.push(Error::UnknownTag {
+                    kind,
+                    tag: tag.to_string(),
+                    location: location.clone(),
+                    similar,
+                });
+            }
+        }
+    }
+}
+
+impl<'a> Visitor<'a, Name> for Checker<'a> {
+    fn statement(&mut self, statement: &'a Statement<Name>) {
+        if let StatementKind::Invoke(Invoke {
+            name,
+            producers,
+            consumers,
+        }) = &statement.kind
+        {
+            // Unknown definitions are reported by name resolution.
+            if let Some(definition) = self.definitions.get(name) {
+                let expected = Arity {
+                    producers: definition.parameters.len(),
+                    consumers: definition.returns.len(),
+                };
+                let actual = Arity {
+                    producers: producers.len(),
+                    consumers: consumers.len(),
+                };
+                self.compare(
+                    &name.text,
+                    expected,
+                    actual,
+                    &statement.location,
+                    &definition.location,
+                );
+            }
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn producer(&mut self, producer: &'a Producer<Name>) {
+        match &producer.kind {
+            ProducerKind::Construct(Construct {
+                tag,
+                producers,
+                consumers,
+            }) => {
+                let actual = Arity {
+                    producers: producers.len(),
+                    consumers: consumers.len(),
+                };
+                self.tag(DeclarationKind::Data, tag, actual, &producer.location);
+            }
+            ProducerKind::Comatch(Comatch { clauses }) => {
+                for clause in clauses {
+                    let Copattern {
+                        tag,
+                        parameters,
+                        returns,
+                    } = &clause.copattern;
+                    let actual = Arity {
+                        producers: parameters.len(),
+                        consumers: returns.len(),
+                    };
+                    self.tag(DeclarationKind::Codata, tag, actual, &clause.location);
+                    self.statement(&clause.body);
+                }
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_producer(self, producer);
+    }
+
+    fn consumer(&mut self, consumer: &'a Consumer<Name>) {
+        match &consumer.kind {
+            ConsumerKind::Destruct(Destruct {
+                tag,
+                producers,
+                consumers,
+            }) => {
+                let actual = Arity {
+                    producers: producers.len(),
+                    consumers: consumers.len(),
+                };
+                self.tag(DeclarationKind::Codata, tag, actual, &consumer.location);
+            }
+            ConsumerKind::Match(Match { clauses }) => {
+                for clause in clauses {
+                    let Pattern {
+                        tag,
+                        parameters,
+                        returns,
+                    } = &clause.pattern;
+                    let actual = Arity {
+                        producers: parameters.len(),
+                        consumers: returns.len(),
+                    };
+                    self.tag(DeclarationKind::Data, tag, actual, &clause.location);
+                    self.statement(&clause.body);
+                }
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_consumer(self, consumer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::tests::program;
+
+    fn check(source: &str) -> Vec<Error> {
+        Checker::new(&program(source)).check()
+    }
+
+    const LIST: &str = "data List[a] { Nil(;), Cons(x: a, xs: List[a];) }\n";
+
+    #[test]
+    fn matching_arities() {
+        let source = format!(
+            "{}def f(l; k) = l | match {{ Nil(;) -> invoke[g](1; k), Cons(x, xs;) -> x | k }}\n\
+             def g(x; k) = Cons(x, Nil(;);) | k",
+            LIST
+        );
+        assert!(check(&source).is_empty());
+    }
+
+    #[test]
+    fn mismatches() {
+        let source = format!(
+            "{}def f(l; k) = l | match {{ Nil(;) -> invoke[g](1, 2; k), Cons(x;) -> Cons(x;) | k }}\n\
+             def g(x; k) = x | k",
+            LIST
+        );
+        let errors = check(&source);
+        let mismatches: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                Error::Mismatch {
+                    name,
+                    expected,
+                    actual,
+                    definition,
+                    ..
+                } => (
+                    name.as_str(),
+                    (expected.producers, actual.producers),
+                    &source[definition.start..definition.start + 1],
+                ),
+                error => panic!("expected a mismatch, got {:?}", error),
+            })
+            .collect();
+        assert_eq!(
+            mismatches,
+            [
+                ("g", (1, 2), "d"),
+                ("Cons", (2, 1), "C"),
+                ("Cons", (2, 1), "C")
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_tag() {
+        let source = format!("{}def f(; k) = Con(1, Nil(;);) | k", LIST);
+        let [Error::UnknownTag {
+            kind: DeclarationKind::Data,
+            tag,
+            similar: Some((similar, _)),
+            ..
+        }] = &check(&source)[..]
+        else {
+            panic!("expected an unknown constructor");
+        };
+        assert_eq!((tag.as_str(), similar.as_str()), ("Con", "Cons"));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::core_ir::*;
+use crate::location;
+use crate::machine::Strategy;
+use crate::suggest;
+use ariadne::Label;
+use ariadne::Report;
+use ariadne::ReportKind;
+use thiserror::Error;
+
+/// Known attributes with the number of arguments they take.
+const ATTRIBUTES: &[(&str, usize)] = &[("strategy", 1)];
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unknown attribute `@{name}`")]
+    Unknown {
+        name: String,
+        location: location::Location,
+        similar: Option<&'static str>,
+    },
+    #[error("`@{name}` takes {expected} argument{}, but {actual} {} given",
+        if *.expected == 1 { "" } else { "s" },
+        if *.actual == 1 { "is" } else { "are" })]
+    Arguments {
+        name: String,
+        expected: usize,
+        actual: usize,
+        location: location::Location,
+    },
+    #[error("unknown strategy `{strategy}`")]
+    UnknownStrategy {
+        strategy: String,
+        location: location::Location,
+    },
+    #[error("duplicate attribute `@{name}`")]
+    Duplicate {
+        name: String,
+        location: location::Location,
+        previous: location::Location,
+    },
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::Unknown {
+                location, similar, ..
+            } => {
+                let mut report = Report::build(ReportKind::Error, location.clone())
+                    .with_message(message)
+                    .with_label(Label::new(location).with_message("not a known attribute"));
+                if let Some(similar) = similar {
+                    report = report.with_help(format!("did you mean `@{}`?", similar));
+                }
+                report.finish()
+            }
+            Error::Arguments {
+                actual, location, ..
+            } => Report::build(ReportKind::Error, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message(format!("{} given here", actual)))
+                .finish(),
+            Error::UnknownStrategy { location, .. } => {
+                Report::build(ReportKind::Error, location.clone())
+                    .with_message(message)
+                    .with_label(Label::new(location).with_message("in this attribute"))
+                    .with_help("expected `cbv`, `cbn` or `need`")
+                    .finish()
+            }
+            Error::Duplicate {
+                location, previous, ..
+            } => Report::build(ReportKind::Error, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message("duplicate"))
+                .with_label(Label::new(previous).with_message("first given here"))
+                .finish(),
+        }
+    }
+}
+
+/// The strategy a definition asks for with `@strategy(...)`, if any.
+pub fn strategy<N>(definition: &Definition<N>) -> Option<Strategy> {
+    definition
+        .attributes
+        .iter()
+        .find(|attribute| attribute.name == "strategy")
+        .and_then(|attribute| attribute.arguments.first()?.parse().ok())
+}
+
+/// Checks that the attributes of every definition are known and well-formed.
+pub struct Checker<'a, N> {
+    program: &'a Program<N>,
+    errors: Vec<Error>,
+}
+
+impl<'a, N> Checker<'a, N> {
+    pub fn new(program: &'a Program<N>) -> Self {
+        Self {
+            program,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn check(mut self) -> Vec<Error> {
+        for definition in &self.program.definitions {
+            for (i, attribute) in definition.attributes.iter().enumerate() {
+                if let Some(previous) = definition.attributes[..i]
+                    .iter()
+                    .find(|previous| previous.name == attribute.name)
+                {
+                    self.errors.push(Error::Duplicate {
+                        name: attribute.name.clone(),
+                        location: attribute.location.clone(),
+                        previous: previous.location.clone(),
+                    });
+                    continue;
+                }
+                self.attribute(attribute);
+            }
+        }
+        self.errors
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        let Some(&(_, expected)) = ATTRIBUTES.iter().find(|(name, _)| *name == attribute.name)
+        else {
+            self.errors.push(Error::Unknown {
+                name: attribute.name.clone(),
+                location: attribute.location.clone(),
+                similar: suggest::closest(
+                    &attribute.name,
+                    ATTRIBUTES.iter().map(|&(name, _)| (name, name)),
+                ),
+            });
+            return;
+        };
+        if attribute.arguments.len() != expected {
+            self.errors.push(Error::Arguments {
+                name: attribute.name.clone(),
+                expected,
+                actual: attribute.arguments.len(),
+                location: attribute.location.clone(),
+            });
+            return;
+        }
+        if attribute.name == "strategy" && attribute.arguments[0].parse::<Strategy>().is_err() {
+            self.errors.push(Error::UnknownStrategy {
+                strategy: attribute.arguments[0].clone(),
+                location: attribute.location.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::tests::program;
+
+    fn messages(source: &str) -> Vec<String> {
+        Checker::new(&program(source))
+            .check()
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn strategies() {
+        let program = program("@strategy(need)\ndef f(; k) = 1 | k\ndef g(; k) = 1 | k");
+        let strategies: Vec<_> = program.definitions.iter().map(strategy).collect();
+        assert_eq!(strategies, [Some(Strategy::Need), None]);
+        assert!(Checker::new(&program).check().is_empty());
+    }
+
+    #[test]
+    fn malformed_attributes() {
+        assert_eq!(
+            messages("@strategie(cbn)\ndef f(; k) = 1 | k"),
+            ["unknown attribute `@strategie`"]
+        );
+        assert_eq!(
+            messages("@strategy(cbn, cbv)\ndef f(; k) = 1 | k"),
+            ["`@strategy` takes 1 argument, but 2 are given"]
+        );
+        assert_eq!(
+            messages("@strategy(lazy)\ndef f(; k) = 1 | k"),
+            ["unknown strategy `lazy`"]
+        );
+        assert_eq!(
+            messages("@strategy(cbn)\n@strategy(cbv)\ndef f(; k) = 1 | k"),
+            ["duplicate attribute `@strategy`"]
+        );
+    }
+
+    #[test]
+    fn unknown_attributes_suggest_a_known_one() {
+        let errors = Checker::new(&program("@stratgy(cbn)\ndef f(; k) = 1 | k")).check();
+        assert!(
+            matches!(
+                &errors[..],
+                [Error::Unknown {
+                    similar: Some("strategy"),
+                    ..
+                }]
+            ),
+            "{:?}",
+            errors
+        );
+    }
+}
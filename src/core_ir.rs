@@ -1,178 +1,389 @@
 use crate::location;
+use serde::{Deserialize, Serialize};
 
 // Core IR
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Producer<N> {
     pub location: location::Location,
     pub kind: ProducerKind<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Variable<N> {
     pub name: N,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Do<N> {
-    pub name: N,
+    pub binder: Binder<N>,
     pub body: Box<Statement<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Construct<N> {
     pub tag: String,
     pub producers: Vec<Producer<N>>,
     pub consumers: Vec<Consumer<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Comatch<N> {
     pub clauses: Vec<Coclause<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum ProducerKind<N> {
     Variable(Variable<N>),
     Literal(Literal),
     Do(Do<N>),
     Construct(Construct<N>),
     Comatch(Comatch<N>),
+    /// Placeholder for a producer that failed to parse.
+    #[serde(skip)]
+    Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Literal {
     Int(i64),
-    Float(f64),
+    Float(#[serde(with = "non_finite")] f64),
     Bool(bool),
     String(String),
 }
 
-#[derive(Debug)]
+/// S-expression readers have no common syntax for infinities and NaN,
+/// so they are serialized as the strings `"+inf.0"`, `"-inf.0"` and `"+nan.0"`,
+/// which Racket's `string->number` reads back as floats.
+mod non_finite {
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_nan() {
+            serializer.serialize_str("+nan.0")
+        } else if value.is_infinite() {
+            serializer.serialize_str(if *value > 0.0 { "+inf.0" } else { "-inf.0" })
+        } else {
+            serializer.serialize_f64(*value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = f64;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a number, \"+inf.0\", \"-inf.0\" or \"+nan.0\"")
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E> {
+                Ok(value)
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<f64, E> {
+                Ok(value as f64)
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<f64, E> {
+                Ok(value as f64)
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E> {
+                match value {
+                    "+inf.0" => Ok(f64::INFINITY),
+                    "-inf.0" => Ok(f64::NEG_INFINITY),
+                    "+nan.0" => Ok(f64::NAN),
+                    _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Coclause<N> {
     pub location: location::Location,
     pub copattern: Copattern<N>,
     pub body: Statement<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Copattern<N> {
     pub tag: String,
-    pub parameters: Vec<N>,
-    pub returns: Vec<N>,
+    pub parameters: Vec<Binder<N>>,
+    pub returns: Vec<Binder<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Consumer<N> {
     pub location: location::Location,
     pub kind: ConsumerKind<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Then<N> {
-    pub name: N,
+    pub binder: Binder<N>,
     pub body: Box<Statement<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Destruct<N> {
     pub tag: String,
     pub producers: Vec<Producer<N>>,
     pub consumers: Vec<Consumer<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Match<N> {
     pub clauses: Vec<Clause<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum ConsumerKind<N> {
     Finish,
     Variable(Variable<N>),
     Then(Then<N>),
     Destruct(Destruct<N>),
     Match(Match<N>),
+    /// Placeholder for a consumer that failed to parse.
+    #[serde(skip)]
+    Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Clause<N> {
     pub location: location::Location,
     pub pattern: Pattern<N>,
     pub body: Statement<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Pattern<N> {
     pub tag: String,
-    pub parameters: Vec<N>,
-    pub returns: Vec<N>,
+    pub parameters: Vec<Binder<N>>,
+    pub returns: Vec<Binder<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Statement<N> {
     pub location: location::Location,
     pub kind: StatementKind<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Cut<N> {
     pub producer: Producer<N>,
     pub consumer: Consumer<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Prim<N> {
     pub name: String,
     pub producers: Vec<Producer<N>>,
     pub consumers: Vec<Consumer<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Switch<N> {
     pub scrutinee: Producer<N>,
     pub branches: Vec<Branch<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Invoke<N> {
     pub name: N,
     pub producers: Vec<Producer<N>>,
     pub consumers: Vec<Consumer<N>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum StatementKind<N> {
     Cut(Cut<N>),
     Prim(Prim<N>),
     Switch(Switch<N>),
     Invoke(Invoke<N>),
+    /// Placeholder for a statement that failed to parse.
+    #[serde(skip)]
+    Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Branch<N> {
     pub location: location::Location,
     pub kind: BranchKind<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LiteralBranch<N> {
     pub literal: Literal,
     pub body: Statement<N>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum BranchKind<N> {
     LiteralBranch(LiteralBranch<N>),
     DefaultBranch(Statement<N>),
 }
 
-#[derive(Debug)]
+/// A type: `Int`, `Float`, `Bool`, `String`, a declared data or codata type
+/// applied to its arguments such as `List[Int]`, or a type variable such as `a`.
+/// A consumer of type `T` consumes producers of type `T`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Type {
+    Named(String, Vec<Type>),
+    Variable(String),
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Named(name, arguments) if arguments.is_empty() => write!(f, "{}", name),
+            Type::Named(name, arguments) => {
+                write!(f, "{}[", name)?;
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, "]")
+            }
+            Type::Variable(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A variable or covariable where it is bound.
+/// Only the binders of definitions and signatures are annotated with a type.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Binder<N> {
+    pub location: location::Location,
+    pub name: N,
+    pub ty: Option<Type>,
+}
+
+/// An attribute of a definition, such as `@strategy(cbn)`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Attribute {
+    pub location: location::Location,
+    pub name: String,
+    pub arguments: Vec<String>,
+}
+
+impl std::fmt::Display for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "@{}", self.name)?;
+        if !self.arguments.is_empty() {
+            write!(f, "({})", self.arguments.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Definition<N> {
     pub location: location::Location,
+    #[serde(default)]
+    pub attributes: Vec<Attribute>,
     pub name: N,
-    pub parameters: Vec<N>,
-    pub returns: Vec<N>,
+    pub parameters: Vec<Binder<N>>,
+    pub returns: Vec<Binder<N>>,
     pub body: Statement<N>,
 }
 
-pub type Program<N> = Vec<Definition<N>>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeclarationKind {
+    Data,
+    Codata,
+}
+
+/// A `data` type with its constructors, or a `codata` type with its destructors.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Declaration {
+    pub location: location::Location,
+    pub kind: DeclarationKind,
+    pub name: String,
+    /// Type parameters, such as `a` in `data List[a] { ... }`.
+    pub parameters: Vec<String>,
+    pub signatures: Vec<Signature>,
+}
+
+/// A constructor or destructor.
+/// The names of its parameters and returns only document it;
+/// their numbers are its producer and consumer arities.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Signature {
+    pub location: location::Location,
+    pub tag: String,
+    pub parameters: Vec<Binder<String>>,
+    pub returns: Vec<Binder<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Program<N> {
+    pub declarations: Vec<Declaration>,
+    pub definitions: Vec<Definition<N>>,
+}
+
+impl<N> Program<N> {
+    /// Finds the constructor (for `Data`) or destructor (for `Codata`) named `tag`
+    /// together with the declaration it belongs to.
+    pub fn signature(
+        &self,
+        kind: DeclarationKind,
+        tag: &str,
+    ) -> Option<(&Declaration, &Signature)> {
+        self.declarations
+            .iter()
+            .filter(|declaration| declaration.kind == kind)
+            .find_map(|declaration| {
+                declaration
+                    .signatures
+                    .iter()
+                    .find(|signature| signature.tag == tag)
+                    .map(|signature| (declaration, signature))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser, printer};
+
+    fn parse(source: &str) -> Program<String> {
+        let (program, errors) =
+            parser::Parser::new(lexer::Lexer::new("test.coco".to_string(), source)).parse();
+        assert!(errors.is_empty(), "{:?}", errors);
+        program
+    }
+
+    #[test]
+    fn sexp_round_trip() {
+        let source = "\
+data List[a] { Nil(;), Cons(x: a, xs: List[a];) }
+
+def main(; k) =
+    Cons(do a 1 | a, Nil(;);) | match {
+        Nil(;) -> switch -inf { nan -> \"a\\n\" | k, _ -> true | k },
+        Cons(x, xs;) -> x | then y prim[print](y; k),
+    }
+";
+        let program = parse(source);
+        let sexp = serde_lexpr::to_string(&program).unwrap();
+        let loaded: Program<String> = serde_lexpr::from_str(&sexp).unwrap();
+        assert_eq!(printer::Printer::default().print(&loaded), source);
+        assert_eq!(serde_lexpr::to_string(&loaded).unwrap(), sexp);
+    }
+
+    #[test]
+    fn error_nodes_are_not_loaded() {
+        let sexp = serde_lexpr::to_string(&parse("def main(; k) = x | k")).unwrap();
+        let sexp = sexp.replace("(kind Variable (name . \"x\"))", "(kind . Error)");
+        assert!(sexp.contains("Error"));
+        assert!(serde_lexpr::from_str::<Program<String>>(&sexp).is_err());
+    }
+}
@@ -0,0 +1,539 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::core_ir::*;
+use crate::location;
+use crate::machine::{Binding, Control, Machine};
+use crate::name::Name;
+use crate::suggest;
+use ariadne::{Color, Label, Report, ReportKind, Source};
+
+const HELP: &str = "\
+Commands:
+    step [N], s [N]         perform one reduction, or N
+    continue, c             run until a breakpoint or the end
+    back [N]                undo one reduction, or N
+    bound COVAR             go back to where the covariable COVAR in scope was bound
+    invoked COVAR           run until the covariable COVAR in scope is passed a value
+    break TARGET, b TARGET  stop before running TARGET, a definition or a line
+    delete TARGET, d TARGET remove the breakpoint at TARGET
+    breakpoints             list the breakpoints
+    env, e                  show the variables and covariables in scope
+    where, w                show the next reduction
+    help, h                 show this help
+    quit, q                 stop debugging
+
+An empty line performs one reduction.";
+
+/// The reduction the machine performs next, at the statement it belongs to.
+pub struct Redex {
+    control: String,
+    step: usize,
+    location: location::Location,
+}
+
+pub fn redex(machine: &Machine) -> Redex {
+    let location = match machine.control() {
+        Control::Statement(statement, _) => &statement.location,
+        Control::Cut(..) | Control::Finished(_) => machine.location(),
+    };
+    Redex {
+        control: machine.control().to_string(),
+        step: machine.steps(),
+        location: location.clone(),
+    }
+}
+
+impl fmt::Display for Redex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "step {}: {}", self.step, self.control)
+    }
+}
+
+/// Convert Redex to a ariadne::Report.
+impl From<Redex> for Report<'_, location::Location> {
+    fn from(redex: Redex) -> Self {
+        Report::build(
+            ReportKind::Custom("Step", Color::Cyan),
+            redex.location.clone(),
+        )
+        .with_message(redex.control)
+        .with_label(
+            Label::new(redex.location)
+                .with_message(format!("step {}", redex.step))
+                .with_color(Color::Cyan),
+        )
+        .finish()
+    }
+}
+
+/// Where the debugger stops.
+#[derive(Debug, PartialEq)]
+pub enum Breakpoint {
+    /// Before the body of the definition with this name runs.
+    Definition(String),
+    /// Before a statement starting on this line, counting from 1, runs.
+    Line(usize),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Definition(name) => write!(f, "definition `{}`", name),
+            Breakpoint::Line(line) => write!(f, "line {}", line),
+        }
+    }
+}
+
+/// Runs a machine under the control of commands read from the user.
+pub struct Debugger<'a> {
+    program: &'a Program<Name>,
+    machine: Machine<'a>,
+    file: &'a str,
+    /// The source of the program, unless it was loaded from another format.
+    source: Option<&'a str>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(
+        program: &'a Program<Name>,
+        mut machine: Machine<'a>,
+        file: &'a str,
+        source: Option<&'a str>,
+    ) -> Self {
+        machine.record();
+        Self {
+            program,
+            machine,
+            file,
+            source,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// Reads commands from `input` until it ends or the user quits.
+    pub fn run(mut self, input: impl BufRead) -> io::Result<()> {
+        self.show()?;
+        prompt()?;
+        for line in input.lines() {
+            if !self.command(&line?)? {
+                return Ok(());
+            }
+            prompt()?;
+        }
+        println!();
+        Ok(())
+    }
+
+    /// Runs a command. Returns false if the user quits.
+    fn command(&mut self, line: &str) -> io::Result<bool> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] | ["s" | "step"] => self.step(1)?,
+            ["s" | "step", count] => match count.parse() {
+                Ok(count) => self.step(count)?,
+                Err(_) => println!("expected a number of steps, not `{}`", count),
+            },
+            ["c" | "continue"] => self.resume()?,
+            ["back"] => self.back(1)?,
+            ["back", count] => match count.parse() {
+                Ok(count) => self.back(count)?,
+                Err(_) => println!("expected a number of steps, not `{}`", count),
+            },
+            ["bound", covariable] => match self.machine.bound(covariable) {
+                Some(step) => {
+                    self.rewind(step);
+                    self.show()?;
+                }
+                None => println!("no covariable `{}` is in scope", covariable),
+            },
+            ["invoked", covariable] => self.invoked(covariable)?,
+            ["b" | "break", target] => {
+                if let Some(breakpoint) = self.breakpoint(target) {
+                    println!("breakpoint at {}", breakpoint);
+                    if !self.breakpoints.contains(&breakpoint) {
+                        self.breakpoints.push(breakpoint);
+                    }
+                }
+            }
+            ["d" | "delete", target] => {
+                if let Some(breakpoint) = self.breakpoint(target) {
+                    match self.breakpoints.iter().position(|b| *b == breakpoint) {
+                        Some(i) => {
+                            self.breakpoints.remove(i);
+                            println!("deleted the breakpoint at {}", breakpoint);
+                        }
+                        None => println!("no breakpoint at {}", breakpoint),
+                    }
+                }
+            }
+            ["breakpoints"] if self.breakpoints.is_empty() => println!("no breakpoints"),
+            ["breakpoints"] => {
+                for breakpoint in &self.breakpoints {
+                    println!("{}", breakpoint);
+                }
+            }
+            ["e" | "env"] => self.env(),
+            ["w" | "where"] => self.show()?,
+            ["h" | "help"] => println!("{}", HELP),
+            ["q" | "quit"] => return Ok(false),
+            _ => println!("unknown command `{}`, try `help`", line.trim()),
+        }
+        Ok(true)
+    }
+
+    /// Parses a line number or the name of a definition.
+    fn breakpoint(&self, target: &str) -> Option<Breakpoint> {
+        if let Ok(line) = target.parse() {
+            if self.source.is_none() {
+                println!("line breakpoints need the source of the program");
+                return None;
+            }
+            return Some(Breakpoint::Line(line));
+        }
+        let names = self
+            .program
+            .definitions
+            .iter()
+            .map(|definition| definition.name.text.as_str());
+        if names.clone().any(|name| name == target) {
+            return Some(Breakpoint::Definition(target.to_string()));
+        }
+        match suggest::closest(target, names.map(|name| (name, name))) {
+            Some(similar) => println!(
+                "no definition named `{}`, did you mean `{}`?",
+                target, similar
+            ),
+            None => println!("no definition named `{}`", target),
+        }
+        None
+    }
+
+    fn finished(&self) -> bool {
+        matches!(self.machine.control(), Control::Finished(_))
+    }
+
+    /// Performs up to `count` reductions, stopping early at the end or on an error.
+    fn step(&mut self, count: usize) -> io::Result<()> {
+        for _ in 0..count {
+            if self.finished() {
+                break;
+            }
+            if let Err(error) = self.machine.step() {
+                return self.report(error);
+            }
+        }
+        self.show()
+    }
+
+    /// Runs until the next breakpoint, the end or an error.
+    fn resume(&mut self) -> io::Result<()> {
+        while !self.finished() {
+            if let Err(error) = self.machine.step() {
+                return self.report(error);
+            }
+            if let Some(breakpoint) = self.hit() {
+                println!("stopped at {}", breakpoint);
+                break;
+            }
+        }
+        self.show()
+    }
+
+    /// Undoes up to `count` reductions.
+    fn back(&mut self, count: usize) -> io::Result<()> {
+        if self.machine.steps() == 0 {
+            println!("already at the first step");
+            return Ok(());
+        }
+        self.rewind(self.machine.steps().saturating_sub(count));
+        self.show()
+    }
+
+    /// Goes back to the state after `step` reductions,
+    /// noting the effects of primitives that stay done.
+    fn rewind(&mut self, step: usize) {
+        let effects = self.machine.effects(step..self.machine.steps());
+        self.machine.go_to(step);
+        if !effects.is_empty() {
+            let names = effects
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!("note: going back does not undo the effects of {}", names);
+        }
+    }
+
+    /// Runs until the covalue of `covariable` is passed a value,
+    /// staying put if that never happens.
+    fn invoked(&mut self, covariable: &str) -> io::Result<()> {
+        let Some(covalue) = self.machine.covariable(covariable) else {
+            println!("no covariable `{}` is in scope", covariable);
+            return Ok(());
+        };
+        let start = self.machine.steps();
+        loop {
+            if let Err(error) = self.machine.step() {
+                return self.report(error);
+            }
+            if self.machine.invokes(&covalue) {
+                return self.show();
+            }
+            if self.finished() {
+                println!("`{}` is not passed a value before the end", covariable);
+                self.rewind(start);
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns the breakpoint the machine is at, if any.
+    fn hit(&self) -> Option<&Breakpoint> {
+        let Control::Statement(statement, _) = self.machine.control() else {
+            return None;
+        };
+        self.breakpoints.iter().find(|breakpoint| match breakpoint {
+            Breakpoint::Definition(name) => self
+                .machine
+                .definition_of(statement)
+                .is_some_and(|definition| definition.name.text == *name),
+            Breakpoint::Line(line) => self.line(statement.location.start) == Some(*line),
+        })
+    }
+
+    fn line(&self, offset: usize) -> Option<usize> {
+        let source = self.source?;
+        Some(source.chars().take(offset).filter(|&c| c == '\n').count() + 1)
+    }
+
+    /// Shows the next reduction, or the final value once finished.
+    fn show(&mut self) -> io::Result<()> {
+        if self.finished() {
+            // Runs the thunks in the final value, as `coco run` does before printing it.
+            if let Err(error) = self.machine.run() {
+                return self.report(error);
+            }
+        }
+        self.report(redex(&self.machine))
+    }
+
+    fn env(&self) {
+        match self.machine.control() {
+            Control::Statement(_, env) => {
+                let mut bindings = env.iter().peekable();
+                if bindings.peek().is_none() {
+                    println!("nothing is in scope");
+                }
+                for (name, binding) in bindings {
+                    match binding {
+                        Binding::Value(value) => println!("{} = {}", name.text, value),
+                        Binding::Covalue(covalue) => println!("{} = {}", name.text, covalue),
+                    }
+                }
+            }
+            Control::Cut(value, covalue) => {
+                println!("the next reduction cuts a value against a covalue:");
+                println!("value = {}", value);
+                println!("covalue = {}", covalue);
+            }
+            Control::Finished(value) => println!("finished with {}", value),
+        }
+    }
+
+    /// Shows `item` at its location in the source, or as plain text without one.
+    fn report<E>(&self, item: E) -> io::Result<()>
+    where
+        E: Into<Report<'static, location::Location>> + fmt::Display,
+    {
+        match self.source {
+            Some(source) => item
+                .into()
+                .print((self.file.to_string(), Source::from(source))),
+            None => {
+                println!("{}: {}", self.file, item);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn prompt() -> io::Result<()> {
+    print!("(coco) ");
+    io::stdout().flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::Strategy;
+    use crate::resolve::tests::program_with;
+    use crate::{focus, name, prim};
+
+    const SOURCE: &str = "def main(; k) = invoke[twice](1; k)\n\
+                          def twice(x; k) = prim[add](x, x; then y y | k)\n";
+
+    /// Resolves and focuses a program that has no errors.
+    fn program(source: &str) -> Program<Name> {
+        let mut names = name::Supply::new();
+        let program = program_with(source, &mut names);
+        focus::Focuser::new(&mut names).focus(program)
+    }
+
+    fn debugger<'a>(
+        program: &'a Program<Name>,
+        primitives: &'a prim::Registry,
+        source: Option<&'a str>,
+    ) -> Debugger<'a> {
+        let machine = Machine::new(
+            program,
+            primitives,
+            &program.definitions[0],
+            Strategy::Value,
+        );
+        Debugger::new(program, machine, "test.coco", source)
+    }
+
+    /// Runs commands, expecting none of them to quit.
+    fn commands(debugger: &mut Debugger, lines: &[&str]) {
+        for line in lines {
+            assert!(debugger.command(line).unwrap(), "`{}` quit", line);
+        }
+    }
+
+    #[test]
+    fn steps() {
+        let program = program(SOURCE);
+        let primitives = prim::Registry::default();
+        let mut debugger = debugger(&program, &primitives, None);
+        commands(&mut debugger, &["step", "", "s 2"]);
+        assert_eq!(debugger.machine.steps(), 4);
+        assert_eq!(debugger.machine.control().to_string(), "2 | finish");
+        commands(&mut debugger, &["step 10"]);
+        assert_eq!(debugger.machine.control().to_string(), "finished with 2");
+        commands(
+            &mut debugger,
+            &["step x", "env", "where", "help", "frobnicate"],
+        );
+        assert!(!debugger.command("quit").unwrap());
+    }
+
+    #[test]
+    fn definition_breakpoints() {
+        let program = program(SOURCE);
+        let primitives = prim::Registry::default();
+        let mut debugger = debugger(&program, &primitives, None);
+        commands(&mut debugger, &["break twice", "b twice", "break twise"]);
+        assert_eq!(
+            debugger.breakpoints,
+            [Breakpoint::Definition("twice".to_string())]
+        );
+        commands(&mut debugger, &["continue"]);
+        assert_eq!(debugger.machine.steps(), 1);
+        assert_eq!(
+            debugger.hit(),
+            Some(&Breakpoint::Definition("twice".to_string()))
+        );
+        commands(&mut debugger, &["delete twice", "c"]);
+        assert!(debugger.breakpoints.is_empty());
+        assert!(debugger.finished());
+    }
+
+    #[test]
+    fn line_breakpoints_need_the_source() {
+        let program = program(SOURCE);
+        let primitives = prim::Registry::default();
+        let mut debugger = debugger(&program, &primitives, None);
+        commands(&mut debugger, &["break 2"]);
+        assert!(debugger.breakpoints.is_empty());
+
+        let mut debugger = self::debugger(&program, &primitives, Some(SOURCE));
+        commands(&mut debugger, &["break 2", "continue"]);
+        assert_eq!(debugger.breakpoints, [Breakpoint::Line(2)]);
+        assert_eq!(debugger.machine.control().to_string(), "prim[add]");
+        // The next statement on line 2, `y | k`, stops again.
+        commands(&mut debugger, &["continue"]);
+        assert_eq!(debugger.machine.steps(), 3);
+    }
+
+    #[test]
+    fn invoked_runs_until_a_covariable_is_used() {
+        let program = program(SOURCE);
+        let primitives = prim::Registry::default();
+        let mut debugger = debugger(&program, &primitives, None);
+        commands(&mut debugger, &["step", "invoked k"]);
+        assert_eq!(debugger.machine.control().to_string(), "2 | finish");
+        commands(&mut debugger, &["invoked nothing"]);
+        assert_eq!(debugger.machine.steps(), 4);
+    }
+
+    #[test]
+    fn back_undoes_reductions() {
+        let program = program(SOURCE);
+        let primitives = prim::Registry::default();
+        let mut debugger = debugger(&program, &primitives, None);
+        commands(&mut debugger, &["back"]);
+        assert_eq!(debugger.machine.steps(), 0);
+        let mut controls = Vec::new();
+        for _ in 0..4 {
+            controls.push(debugger.machine.control().to_string());
+            commands(&mut debugger, &["step"]);
+        }
+        commands(&mut debugger, &["back 3"]);
+        assert_eq!(debugger.machine.steps(), 1);
+        commands(&mut debugger, &["back 10"]);
+        assert_eq!(debugger.machine.steps(), 0);
+        // Going forward again replays the same reductions.
+        for control in &controls {
+            assert_eq!(&debugger.machine.control().to_string(), control);
+            commands(&mut debugger, &["step"]);
+        }
+        commands(&mut debugger, &["back x"]);
+        assert_eq!(debugger.machine.steps(), 4);
+    }
+
+    #[test]
+    fn bound_goes_back_to_the_binding() {
+        let program = program(SOURCE);
+        let primitives = prim::Registry::default();
+        let mut debugger = debugger(&program, &primitives, None);
+        // `k` of `twice` is bound by invoking it.
+        commands(&mut debugger, &["step 3", "bound k"]);
+        assert_eq!(debugger.machine.steps(), 0);
+        assert_eq!(debugger.machine.control().to_string(), "invoke[twice]");
+        commands(&mut debugger, &["step 3", "bound nothing"]);
+        assert_eq!(debugger.machine.steps(), 3);
+    }
+
+    #[test]
+    fn effects_are_not_undone() {
+        let source = "def main(; k) = prim[show](1; then s prim[print](s; k))\n";
+        let program = program(source);
+        let primitives = prim::Registry::default();
+        let mut debugger = debugger(&program, &primitives, None);
+        commands(&mut debugger, &["step 3"]);
+        assert_eq!(debugger.machine.effects(0..3), ["print"]);
+        assert!(debugger.machine.effects(0..2).is_empty());
+        commands(&mut debugger, &["back 3", "step 3"]);
+        assert_eq!(debugger.machine.effects(0..3), ["print"]);
+    }
+
+    #[test]
+    fn redexes_are_located() {
+        let program = program(SOURCE);
+        let primitives = prim::Registry::default();
+        let machine = Machine::new(
+            &program,
+            &primitives,
+            &program.definitions[0],
+            Strategy::Value,
+        );
+        let redex = redex(&machine);
+        assert_eq!(redex.to_string(), "step 0: invoke[twice]");
+        assert_eq!(
+            &SOURCE[redex.location.start..redex.location.end],
+            "invoke[twice](1; k)"
+        );
+    }
+}
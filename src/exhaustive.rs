@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use crate::core_ir::*;
+use crate::location;
+use crate::name::Name;
+use crate::printer;
+use crate::visit::{self, Visitor};
+use ariadne::Label;
+use ariadne::Report;
+use ariadne::ReportKind;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("non-exhaustive {construct}: {} not covered", describe_missing(.missing))]
+    NonExhaustive {
+        /// `match` or `comatch`.
+        construct: &'static str,
+        /// The missing constructors or destructors.
+        missing: Vec<String>,
+        location: location::Location,
+    },
+    #[error("unreachable clause for `{tag}`")]
+    UnreachableClause {
+        tag: String,
+        location: location::Location,
+        previous: location::Location,
+    },
+    #[error("`switch` on `{ty}` has no default branch")]
+    MissingDefault {
+        ty: &'static str,
+        location: location::Location,
+    },
+    #[error("non-exhaustive switch: {} not covered", describe_missing(.missing))]
+    NonExhaustiveSwitch {
+        missing: Vec<String>,
+        location: location::Location,
+    },
+    #[error("`switch` has no branches")]
+    EmptySwitch { location: location::Location },
+    #[error("duplicate branch for `{literal}`")]
+    DuplicateLiteral {
+        literal: String,
+        location: location::Location,
+        previous: location::Location,
+    },
+    #[error("unreachable branch after the default branch")]
+    UnreachableBranch {
+        location: location::Location,
+        default: location::Location,
+    },
+}
+
+fn describe_missing(missing: &[String]) -> String {
+    let quoted = missing
+        .iter()
+        .map(|tag| format!("`{}`", tag))
+        .collect::<Vec<_>>();
+    match quoted.as_slice() {
+        [one] => format!("{} is", one),
+        [init @ .., last] => format!("{} and {} are", init.join(", "), last),
+        [] => unreachable!("only missing tags are reported"),
+    }
+}
+
+impl Error {
+    /// Non-exhaustive matches and comatches are errors, everything else is a warning.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Error::NonExhaustive { .. })
+    }
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        let kind = if error.is_error() {
+            ReportKind::Error
+        } else {
+            ReportKind::Warning
+        };
+        match error {
+            Error::NonExhaustive {
+                construct,
+                location,
+                ..
+            } => Report::build(kind, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message(format!("in this {}", construct)))
+                .finish(),
+            Error::UnreachableClause {
+                tag,
+                location,
+                previous,
+            } => Report::build(kind, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message("never used"))
+                .with_label(Label::new(previous).with_message(format!("`{}` is handled here", tag)))
+                .finish(),
+            Error::MissingDefault { location, .. } => Report::build(kind, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message("values not listed are not handled"))
+                .with_help("add a branch `_ -> ...`")
+                .finish(),
+            Error::NonExhaustiveSwitch { location, .. } | Error::EmptySwitch { location } => {
+                Report::build(kind, location.clone())
+                    .with_message(message)
+                    .with_label(Label::new(location).with_message("in this switch"))
+                    .finish()
+            }
+            Error::DuplicateLiteral {
+                location, previous, ..
+            } => Report::build(kind, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message("never used"))
+                .with_label(Label::new(previous).with_message("already handled here"))
+                .finish(),
+            Error::UnreachableBranch { location, default } => Report::build(kind, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message("never used"))
+                .with_label(Label::new(default).with_message("every value is handled here"))
+                .finish(),
+        }
+    }
+}
+
+/// Floats are compared by value, except that `nan` equals itself;
+/// other literals are equal only if they have the same kind and value.
+pub fn same_literal(left: &Literal, right: &Literal) -> bool {
+    match (left, right) {
+        (Literal::Int(left), Literal::Int(right)) => left == right,
+        (Literal::Float(left), Literal::Float(right)) => {
+            left == right || (left.is_nan() && right.is_nan())
+        }
+        (Literal::Bool(left), Literal::Bool(right)) => left == right,
+        (Literal::String(left), Literal::String(right)) => left == right,
+        _ => false,
+    }
+}
+
+/// Checks that every `match` and `comatch` covers all tags of its type exactly once,
+/// and that every `switch` covers its literals without duplicates.
+/// The types of `match`es and `comatch`es are those found by the type checker.
+pub struct Checker<'a> {
+    program: &'a Program<Name>,
+    types: &'a HashMap<location::Location, String>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(program: &'a Program<Name>, types: &'a HashMap<location::Location, String>) -> Self {
+        Self {
+            program,
+            types,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Returns errors and warnings together; see `Error::is_error`.
+    pub fn check(mut self) -> Vec<Error> {
+        let program = self.program;
+        for definition in &program.definitions {
+            self.statement(&definition.body);
+        }
+        self.errors
+    }
+
+    /// Checks the tags of the clauses of a `match` or `comatch`,
+    /// given as tag and clause location.
+    fn clauses(
+        &mut self,
+        kind: DeclarationKind,
+        clauses: &[(&str, &location::Location)],
+        location: &location::Location,
+    ) {
+        let mut seen: Vec<(&str, &location::Location)> = Vec::new();
+        for &(tag, clause) in clauses {
+            match seen.iter().find(|(seen, _)| *seen == tag) {
+                Some((_, previous)) => self.errors.push(Error::UnreachableClause {
+                    tag: tag.to_string(),
+                    location: clause.clone(),
+                    previous: (*previous).clone(),
+                }),
+                None => seen.push((tag, clause)),
+            }
+        }
+
+        // A type the checker left unknown, as of an empty `match` on an unused variable, has no tags to miss.
+        let Some(declaration) = self.types.get(location).and_then(|name| {
+            self.program
+                .declarations
+                .iter()
+                .find(|declaration| declaration.kind == kind && declaration.name == *name)
+        }) else {
+            return;
+        };
+        let missing: Vec<_> = declaration
+            .signatures
+            .iter()
+            .filter(|signature| !seen.iter().any(|(tag, _)| *tag == signature.tag))
+            .collect();
+        if !missing.is_empty() {
+            self.errors.push(Error::NonExhaustive {
+                construct: match kind {
+                    DeclarationKind::Data => "match",
+                    DeclarationKind::Codata => "comatch",
+                },
+                missing: missing
+                    .iter()
+                    .map(|signature| signature.tag.clone())
+                    .collect(),
+                location: location.clone(),
+            });
+        }
+    }
+
+    fn switch(&mut self, branches: &[Branch<Name>], location: &location::Location) {
+        let mut default: Option<&location::Location> = None;
+        let mut literals: Vec<(&Literal, &location::Location)> = Vec::new();
+        for branch in branches {
+            if let Some(default) = default {
+                self.errors.push(Error::UnreachableBranch {
+                    location: branch.location.clone(),
+                    default: default.clone(),
+                });
+                continue;
+            }
+            match &branch.kind {
+                BranchKind::LiteralBranch(LiteralBranch { literal, .. }) => {
+                    match literals
+                        .iter()
+                        .find(|(seen, _)| same_literal(seen, literal))
+                    {
+                        Some((_, previous)) => self.errors.push(Error::DuplicateLiteral {
+                            literal: printer::literal(literal),
+                            location: branch.location.clone(),
+                            previous: (*previous).clone(),
+                        }),
+                        None => literals.push((literal, &branch.location)),
+                    }
+                }
+                BranchKind::DefaultBranch(_) => default = Some(&branch.location),
+            }
+        }
+        if default.is_some() {
+            return;
+        }
+
+        match literals.first().map(|(literal, _)| literal) {
+            Some(Literal::Bool(_)) => {
+                let missing: Vec<_> = [true, false]
+                    .into_iter()
+                    .filter(|b| {
+                        !literals
+                            .iter()
+                            .any(|(literal, _)| matches!(literal, Literal::Bool(l) if l == b))
+                    })
+                    .map(|b| b.to_string())
+                    .collect();
+                if !missing.is_empty() {
+                    self.errors.push(Error::NonExhaustiveSwitch {
+                        missing,
+                        location: location.clone(),
+                    });
+                }
+            }
+            Some(literal) => self.errors.push(Error::MissingDefault {
+                ty: match literal {
+                    Literal::Int(_) => "Int",
+                    Literal::Float(_) => "Float",
+                    Literal::String(_) => "String",
+                    Literal::Bool(_) => unreachable!(),
+                },
+                location: location.clone(),
+            }),
+            None => self.errors.push(Error::EmptySwitch {
+                location: location.clone(),
+            }),
+        }
+    }
+}
+
+impl<'a> Visitor<'a, Name> for Checker<'a> {
+    fn statement(&mut self, statement: &'a Statement<Name>) {
+        if let StatementKind::Switch(Switch { branches, .. }) = &statement.kind {
+            self.switch(branches, &statement.location);
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn producer(&mut self, producer: &'a Producer<Name>) {
+        if let ProducerKind::Comatch(Comatch { clauses }) = &producer.kind {
+            let tags: Vec<_> = clauses
+                .iter()
+                .map(|clause| (clause.copattern.tag.as_str(), &clause.location))
+                .collect();
+            self.clauses(DeclarationKind::Codata, &tags, &producer.location);
+        }
+        visit::walk_producer(self, producer);
+    }
+
+    fn consumer(&mut self, consumer: &'a Consumer<Name>) {
+        if let ConsumerKind::Match(Match { clauses }) = &consumer.kind {
+            let tags: Vec<_> = clauses
+                .iter()
+                .map(|clause| (clause.pattern.tag.as_str(), &clause.location))
+                .collect();
+            self.clauses(DeclarationKind::Data, &tags, &consumer.location);
+        }
+        visit::walk_consumer(self, consumer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::tests::program;
+    use crate::{prim, typecheck};
+
+    const LIST: &str = "data List[a] { Nil(;), Cons(x: a, xs: List[a];) }\n\
+                        codata Stream[a] { Head(; k: a), Tail(; k: Stream[a]) }\n";
+
+    /// Checks a program that is otherwise well typed.
+    fn check(source: &str) -> Vec<Error> {
+        let program = program(&format!("{}{}", LIST, source));
+        let primitives = prim::Registry::default();
+        let (typing, errors) = typecheck::Checker::new(&program, &primitives).check();
+        assert!(errors.is_empty(), "{:?}", errors);
+        Checker::new(&program, &typing.clauses).check()
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        check(source)
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn complete_matches() {
+        assert!(check(
+            "def len(xs; k) = xs | match { Nil(;) -> 0 | k, Cons(x, ys;) -> 1 | k }\n\
+             def ones(; k) = comatch { Head(; h) -> 1 | h, Tail(; t) -> invoke[ones](; t) } | k"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn missing_tags() {
+        assert_eq!(
+            messages("def head(xs; k) = xs | match { Cons(x, ys;) -> x | k }"),
+            ["non-exhaustive match: `Nil` is not covered"]
+        );
+        assert_eq!(
+            messages("def ones(; k) = comatch { Head(; h) -> 1 | h } | k"),
+            ["non-exhaustive comatch: `Tail` is not covered"]
+        );
+    }
+
+    #[test]
+    fn empty_match_on_a_known_type() {
+        let errors = check("def f(; k) = Nil(;) | match { }");
+        assert!(
+            matches!(&errors[..], [Error::NonExhaustive { missing, .. }] if missing == &["Nil", "Cons"]),
+            "{:?}",
+            errors
+        );
+        // With nothing to say what it matches, an empty match covers every tag.
+        assert!(check("def g(x;) = x | match { }").is_empty());
+    }
+
+    #[test]
+    fn unreachable_clauses() {
+        let errors = check(
+            "def f(xs; k) = xs | match { Nil(;) -> 0 | k, Cons(x, ys;) -> 1 | k, Nil(;) -> 2 | k }",
+        );
+        assert!(
+            matches!(&errors[..], [Error::UnreachableClause { tag, .. }] if tag == "Nil"),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn switches() {
+        assert_eq!(
+            messages("def f(b; k) = switch b { true -> 1 | k }"),
+            ["non-exhaustive switch: `false` is not covered"]
+        );
+        assert_eq!(
+            messages("def f(n; k) = switch n { 1 -> 1 | k, 0x1 -> 2 | k }"),
+            [
+                "duplicate branch for `1`",
+                "`switch` on `Int` has no default branch"
+            ]
+        );
+        assert_eq!(
+            messages("def f(n; k) = switch n { _ -> 1 | k, 2 -> 2 | k }"),
+            ["unreachable branch after the default branch"]
+        );
+        assert!(check("def f(n; k) = switch n { 1 -> 1 | k, _ -> 2 | k }").is_empty());
+    }
+
+    #[test]
+    fn nan_equals_itself() {
+        assert!(same_literal(
+            &Literal::Float(f64::NAN),
+            &Literal::Float(f64::NAN)
+        ));
+        assert!(!same_literal(&Literal::Int(1), &Literal::Float(1.0)));
+    }
+
+    #[test]
+    fn zeros_are_the_same_float() {
+        assert!(same_literal(&Literal::Float(0.0), &Literal::Float(-0.0)));
+        assert_eq!(
+            messages("def f(x; k) = switch x { 0.0 -> 1 | k, -0.0 -> 2 | k, _ -> 3 | k }"),
+            ["duplicate branch for `-0.0`"]
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use crate::core_ir::*;
+use crate::location;
+use crate::name::{Name, Supply};
+
+/// Lifts `do` producers out of argument positions,
+/// so that the machine only ever meets them directly in a cut.
+///
+/// `C(p, do α s, q) | c` becomes `do α s | then x C(p, x, q) | c`,
+/// and likewise for the arguments of destructors, `prim`, `invoke`
+/// and the scrutinee of `switch`. Lifted producers keep their left-to-right order.
+/// Whether the lifted `do` runs before or after the `then` is up to the strategy.
+pub struct Focuser<'a> {
+    names: &'a mut Supply,
+}
+
+impl<'a> Focuser<'a> {
+    pub fn new(names: &'a mut Supply) -> Self {
+        Self { names }
+    }
+
+    pub fn focus(mut self, program: Program<Name>) -> Program<Name> {
+        Program {
+            declarations: program.declarations,
+            definitions: program
+                .definitions
+                .into_iter()
+                .map(|definition| Definition {
+                    body: self.statement(definition.body),
+                    ..definition
+                })
+                .collect(),
+        }
+    }
+
+    fn statement(&mut self, statement: Statement<Name>) -> Statement<Name> {
+        let mut lifted = Vec::new();
+        let kind = match statement.kind {
+            StatementKind::Cut(Cut { producer, consumer }) => {
+                let producer = match producer.kind {
+                    ProducerKind::Do(Do { binder, body }) => Producer {
+                        location: producer.location,
+                        kind: ProducerKind::Do(Do {
+                            binder,
+                            body: Box::new(self.statement(*body)),
+                        }),
+                    },
+                    kind => self.producer(
+                        Producer {
+                            location: producer.location,
+                            kind,
+                        },
+                        &mut lifted,
+                    ),
+                };
+                let consumer = self.consumer(consumer, &mut lifted);
+                StatementKind::Cut(Cut { producer, consumer })
+            }
+            StatementKind::Prim(Prim {
+                name,
+                producers,
+                consumers,
+            }) => {
+                let (producers, consumers) = self.arguments(producers, consumers, &mut lifted);
+                StatementKind::Prim(Prim {
+                    name,
+                    producers,
+                    consumers,
+                })
+            }
+            StatementKind::Switch(Switch {
+                scrutinee,
+                branches,
+            }) => StatementKind::Switch(Switch {
+                scrutinee: self.argument(scrutinee, &mut lifted),
+                branches: branches
+                    .into_iter()
+                    .map(|branch| Branch {
+                        location: branch.location,
+                        kind: match branch.kind {
+                            BranchKind::LiteralBranch(LiteralBranch { literal, body }) => {
+                                BranchKind::LiteralBranch(LiteralBranch {
+                                    literal,
+                                    body: self.statement(body),
+                                })
+                            }
+                            BranchKind::DefaultBranch(body) => {
+                                BranchKind::DefaultBranch(self.statement(body))
+                            }
+                        },
+                    })
+                    .collect(),
+            }),
+            StatementKind::Invoke(Invoke {
+                name,
+                producers,
+                consumers,
+            }) => {
+                let (producers, consumers) = self.arguments(producers, consumers, &mut lifted);
+                StatementKind::Invoke(Invoke {
+                    name,
+                    producers,
+                    consumers,
+                })
+            }
+            StatementKind::Error => StatementKind::Error,
+        };
+        let location = statement.location;
+        lifted.into_iter().rev().fold(
+            Statement {
+                location: location.clone(),
+                kind,
+            },
+            |body, (name, producer)| cut_then(producer, name, body, &location),
+        )
+    }
+
+    /// Focuses a producer in argument position, replacing it by a variable if it is a `do`.
+    fn argument(
+        &mut self,
+        producer: Producer<Name>,
+        lifted: &mut Vec<(Name, Producer<Name>)>,
+    ) -> Producer<Name> {
+        match producer.kind {
+            ProducerKind::Do(Do { binder, body }) => {
+                let variable = self.names.fresh("x".to_string());
+                lifted.push((
+                    variable.clone(),
+                    Producer {
+                        location: producer.location.clone(),
+                        kind: ProducerKind::Do(Do {
+                            binder,
+                            body: Box::new(self.statement(*body)),
+                        }),
+                    },
+                ));
+                Producer {
+                    location: producer.location,
+                    kind: ProducerKind::Variable(Variable { name: variable }),
+                }
+            }
+            kind => self.producer(
+                Producer {
+                    location: producer.location,
+                    kind,
+                },
+                lifted,
+            ),
+        }
+    }
+
+    fn arguments(
+        &mut self,
+        producers: Vec<Producer<Name>>,
+        consumers: Vec<Consumer<Name>>,
+        lifted: &mut Vec<(Name, Producer<Name>)>,
+    ) -> (Vec<Producer<Name>>, Vec<Consumer<Name>>) {
+        let producers = producers
+            .into_iter()
+            .map(|producer| self.argument(producer, lifted))
+            .collect();
+        let consumers = consumers
+            .into_iter()
+            .map(|consumer| self.consumer(consumer, lifted))
+            .collect();
+        (producers, consumers)
+    }
+
+    /// Focuses a producer that is not a `do`.
+    fn producer(
+        &mut self,
+        producer: Producer<Name>,
+        lifted: &mut Vec<(Name, Producer<Name>)>,
+    ) -> Producer<Name> {
+        let kind = match producer.kind {
+            ProducerKind::Construct(Construct {
+                tag,
+                producers,
+                consumers,
+            }) => {
+                let (producers, consumers) = self.arguments(producers, consumers, lifted);
+                ProducerKind::Construct(Construct {
+                    tag,
+                    producers,
+                    consumers,
+                })
+            }
+            ProducerKind::Comatch(Comatch { clauses }) => ProducerKind::Comatch(Comatch {
+                clauses: clauses
+                    .into_iter()
+                    .map(|clause| Coclause {
+                        body: self.statement(clause.body),
+                        ..clause
+                    })
+                    .collect(),
+            }),
+            ProducerKind::Do(_) => unreachable!("`do` is handled by the caller"),
+            kind => kind,
+        };
+        Producer {
+            location: producer.location,
+            kind,
+        }
+    }
+
+    fn consumer(
+        &mut self,
+        consumer: Consumer<Name>,
+        lifted: &mut Vec<(Name, Producer<Name>)>,
+    ) -> Consumer<Name> {
+        let kind = match consumer.kind {
+            ConsumerKind::Then(Then { binder, body }) => ConsumerKind::Then(Then {
+                binder,
+                body: Box::new(self.statement(*body)),
+            }),
+            ConsumerKind::Destruct(Destruct {
+                tag,
+                producers,
+                consumers,
+            }) => {
+                let (producers, consumers) = self.arguments(producers, consumers, lifted);
+                ConsumerKind::Destruct(Destruct {
+                    tag,
+                    producers,
+                    consumers,
+                })
+            }
+            ConsumerKind::Match(Match { clauses }) => ConsumerKind::Match(Match {
+                clauses: clauses
+                    .into_iter()
+                    .map(|clause| Clause {
+                        body: self.statement(clause.body),
+                        ..clause
+                    })
+                    .collect(),
+            }),
+            kind => kind,
+        };
+        Consumer {
+            location: consumer.location,
+            kind,
+        }
+    }
+}
+
+/// Builds `producer | then name body`, binding `name` where `producer` is.
+fn cut_then(
+    producer: Producer<Name>,
+    name: Name,
+    body: Statement<Name>,
+    location: &location::Location,
+) -> Statement<Name> {
+    let binder = producer.location.clone();
+    Statement {
+        location: location.clone(),
+        kind: StatementKind::Cut(Cut {
+            producer,
+            consumer: Consumer {
+                location: location.clone(),
+                kind: ConsumerKind::Then(Then {
+                    binder: Binder {
+                        location: binder,
+                        name,
+                        ty: None,
+                    },
+                    body: Box::new(body),
+                }),
+            },
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer;
+    use crate::resolve::tests::program_with;
+
+    /// Focuses a program and prints it back.
+    fn focused(source: &str) -> String {
+        let mut names = Supply::new();
+        let program = program_with(source, &mut names);
+        let program = Focuser::new(&mut names).focus(program);
+        printer::Printer::new(200).print(&program)
+    }
+
+    #[test]
+    fn lifts_dos_in_order() {
+        assert_eq!(
+            focused("def f(; k) = Cons(do a 1 | a, 2, do b 3 | b;) | k\n"),
+            "def f#0(; k#1) = do a#2 1 | a#2 | then x#4 do b#3 3 | b#3 | then x#5 Cons(x#4, 2, x#5;) | k#1\n"
+        );
+    }
+
+    #[test]
+    fn lifts_from_every_argument_position() {
+        assert_eq!(
+            focused("def f(; k) = prim[add](do a 1 | a, 2; k)\n"),
+            "def f#0(; k#1) = do a#2 1 | a#2 | then x#3 prim[add](x#3, 2; k#1)\n"
+        );
+        assert_eq!(
+            focused("def f(; k) = switch do a true | a { _ -> 1 | k }\n"),
+            "def f#0(; k#1) = do a#2 true | a#2 | then x#3 switch x#3 { _ -> 1 | k#1 }\n"
+        );
+        assert_eq!(
+            focused("def f(c; k) = c | Get(do a 1 | a; k)\n"),
+            "def f#0(c#1; k#2) = do a#3 1 | a#3 | then x#4 c#1 | Get(x#4; k#2)\n"
+        );
+    }
+
+    #[test]
+    fn keeps_dos_that_are_cut() {
+        let source = "def f(; k) = do a 1 | a | then x x | k\n";
+        assert_eq!(
+            focused(source),
+            "def f#0(; k#1) = do a#2 1 | a#2 | then x#3 x#3 | k#1\n"
+        );
+    }
+}
@@ -1,10 +1,96 @@
-use crate::{core_ir, token};
+use crate::{core_ir, location, token};
+use ariadne::{Label, Report, ReportKind};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unexpected character {character:?}")]
+    UnexpectedCharacter {
+        character: char,
+        location: location::Location,
+    },
+    #[error("unterminated string literal")]
+    UnterminatedString { location: location::Location },
+    #[error("integer literal out of range")]
+    IntegerOverflow { location: location::Location },
+    #[error("unterminated block comment")]
+    UnterminatedComment { location: location::Location },
+    #[error("unknown character escape")]
+    InvalidEscape { location: location::Location },
+    #[error("invalid unicode escape")]
+    InvalidUnicodeEscape { location: location::Location },
+    #[error("missing digits in numeric literal")]
+    MissingDigits { location: location::Location },
+    #[error("invalid digit for a base {radix} literal")]
+    InvalidDigit {
+        radix: u32,
+        location: location::Location,
+    },
+}
+
+impl Error {
+    pub fn location(&self) -> &location::Location {
+        match self {
+            Error::UnexpectedCharacter { location, .. }
+            | Error::UnterminatedString { location }
+            | Error::IntegerOverflow { location }
+            | Error::UnterminatedComment { location }
+            | Error::InvalidEscape { location }
+            | Error::InvalidUnicodeEscape { location }
+            | Error::MissingDigits { location }
+            | Error::InvalidDigit { location, .. } => location,
+        }
+    }
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let location = error.location().clone();
+        let label = match &error {
+            Error::UnexpectedCharacter { character: '-', .. } => {
+                "`-` must be followed by `>` or a number".to_string()
+            }
+            Error::UnexpectedCharacter { character, .. } => {
+                format!("{:?} cannot start a token", character)
+            }
+            Error::UnterminatedString { .. } => "missing closing `\"`".to_string(),
+            Error::IntegerOverflow { .. } => {
+                format!("does not fit in {} bits", i64::BITS)
+            }
+            Error::UnterminatedComment { .. } => "missing closing `*/`".to_string(),
+            Error::InvalidEscape { .. } => {
+                "expected one of `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\'` or `\\u{...}`"
+                    .to_string()
+            }
+            Error::InvalidUnicodeEscape { .. } => {
+                "expected 1 to 6 hex digits of a Unicode scalar value in `\\u{...}`".to_string()
+            }
+            Error::MissingDigits { .. } => {
+                "expected digits after the base prefix or exponent".to_string()
+            }
+            Error::InvalidDigit { radix, .. } => {
+                format!("contains a character that is not a base {} digit", radix)
+            }
+        };
+        Report::build(ReportKind::Error, location.clone())
+            .with_message(error.to_string())
+            .with_label(Label::new(location).with_message(label))
+            .finish()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Clone)]
 pub struct Lexer<'a> {
     pub file: String,
+    /// The input from the cursor on.
     pub source: std::str::Chars<'a>,
+    /// The number of characters before the cursor.
     pub cursor: usize,
+    /// Comments skipped so far, in source order.
+    pub trivia: Vec<token::Comment>,
 }
 
 impl<'a> Lexer<'a> {
@@ -13,6 +99,7 @@ impl<'a> Lexer<'a> {
             file,
             source: source.chars(),
             cursor: 0,
+            trivia: Vec::new(),
         }
     }
 
@@ -20,18 +107,26 @@ impl<'a> Lexer<'a> {
         self.cursor
     }
 
-    pub fn set_cursor(&mut self, cursor: usize) {
-        self.cursor = cursor;
-    }
-
     /// Returns the current character without advancing the cursor.
     fn peek(&self) -> Option<char> {
-        self.source.clone().nth(self.cursor)
+        self.source.clone().next()
+    }
+
+    /// Returns the character after the current one without advancing the cursor.
+    fn peek_next(&self) -> Option<char> {
+        self.source.clone().nth(1)
     }
 
     /// Advance the cursor.
     fn advance(&mut self) {
-        self.cursor += 1;
+        if self.source.next().is_some() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Returns the input skipped since `rest` was the input from the cursor on.
+    fn since(&self, rest: &'a str) -> &'a str {
+        &rest[..rest.len() - self.source.as_str().len()]
     }
 
     /// Advances the cursor until the predicate returns false.
@@ -41,137 +136,405 @@ impl<'a> Lexer<'a> {
     where
         P: FnMut(char) -> bool,
     {
-        let start = self.cursor;
+        let rest = self.source.as_str();
         while let Some(c) = self.peek() {
             if !predicate(c) {
                 break;
             }
             self.advance();
         }
-        self.source
-            .clone()
-            .skip(start)
-            .take(self.cursor - start)
-            .collect()
+        self.since(rest).to_string()
+    }
+
+    fn location(&self, start: usize) -> location::Location {
+        location::Location {
+            file: self.file.clone(),
+            start,
+            end: self.cursor,
+        }
+    }
+
+    /// Skips whitespace and comments, recording comments in `trivia`.
+    /// Line comments start with `//` and run to the end of the line.
+    /// Block comments are delimited by `/*` and `*/` and may be nested.
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            let newline_before = self.skip_while(char::is_whitespace).contains('\n');
+            let start = self.cursor;
+            let rest = self.source.as_str();
+            let kind = match (self.peek(), self.peek_next()) {
+                (Some('/'), Some('/')) => {
+                    self.skip_while(|c| c != '\n');
+                    token::CommentKind::Line
+                }
+                (Some('/'), Some('*')) => {
+                    self.advance();
+                    self.advance();
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match (self.peek(), self.peek_next()) {
+                            (Some('/'), Some('*')) => {
+                                self.advance();
+                                self.advance();
+                                depth += 1;
+                            }
+                            (Some('*'), Some('/')) => {
+                                self.advance();
+                                self.advance();
+                                depth -= 1;
+                            }
+                            (Some(_), _) => self.advance(),
+                            (None, _) => {
+                                return Err(Error::UnterminatedComment {
+                                    location: self.location(start),
+                                })
+                            }
+                        }
+                    }
+                    token::CommentKind::Block
+                }
+                _ => return Ok(()),
+            };
+            self.trivia.push(token::Comment {
+                location: self.location(start),
+                kind,
+                newline_before,
+                text: self.since(rest).to_string(),
+            });
+        }
     }
 
-    /// Returns the next token.
+    /// Returns the next token, or a lexical error.
     /// The cursor is positioned at the first character after the token.
-    pub fn next_token(&mut self) -> Option<token::Token> {
-        self.skip_while(char::is_whitespace);
+    /// On error, the offending characters are skipped so that lexing can continue.
+    pub fn next_token(&mut self) -> Option<Result<token::Token>> {
+        if let Err(error) = self.skip_trivia() {
+            return Some(Err(error));
+        }
         let start = self.cursor;
         match self.peek() {
             None => None,
-            Some(c) if is_identifier_start(c) => {
-                let text = self.skip_while(is_identifier_continue);
-                Some(if text == "true" {
-                    token::Token::new(
-                        self.file.clone(),
-                        start,
-                        self.cursor,
-                        token::TokenKind::Literal(core_ir::Literal::Bool(true)),
-                    )
-                } else if text == "false" {
-                    token::Token::new(
-                        self.file.clone(),
-                        start,
-                        self.cursor,
-                        token::TokenKind::Literal(core_ir::Literal::Bool(false)),
-                    )
-                } else {
-                    token::Token::new(
-                        self.file.clone(),
-                        start,
-                        self.cursor,
-                        token::TokenKind::Identifier(text),
-                    )
-                })
+            Some('r') if matches!(self.peek_next(), Some('"' | '#')) => {
+                Some(self.raw_string(start))
             }
-            Some(c) if is_int_start(c) => {
-                let text = self.skip_while(is_int_continue);
-                if self.peek() == Some('.') {
-                    self.advance();
-                    let text = text + "." + &self.skip_while(is_int_continue);
-                    Some(token::Token::new(
-                        self.file.clone(),
-                        start,
-                        self.cursor,
-                        token::TokenKind::Literal(core_ir::Literal::Float(text.parse().unwrap())),
-                    ))
-                } else {
-                    Some(token::Token::new(
-                        self.file.clone(),
-                        start,
-                        self.cursor,
-                        token::TokenKind::Literal(core_ir::Literal::Int(text.parse().unwrap())),
-                    ))
-                }
+            Some('-') if self.peek_next().is_some_and(is_int_start) => Some(self.number(start)),
+            Some('-') if self.looking_at_word("-inf") => {
+                "-inf".chars().for_each(|_| self.advance());
+                Some(Ok(token::Token::new(
+                    self.file.clone(),
+                    start,
+                    self.cursor,
+                    token::TokenKind::Literal(core_ir::Literal::Float(f64::NEG_INFINITY)),
+                )))
             }
-            Some(c) if c == '"' => {
-                self.advance();
-                while self.peek() != None {
-                    if self.peek() == Some('\\') {
-                        self.advance();
-                        self.advance();
-                    }
-                    if self.peek() == Some('"') {
-                        break;
-                    }
-                    self.advance();
-                }
-                if self.peek() != Some('"') {
-                    panic!("unterminated string literal");
-                }
-                self.advance();
-                Some(token::Token::new(
+            Some(c) if is_identifier_start(c) => {
+                let text = self.skip_while(is_identifier_continue);
+                let kind = match text.as_str() {
+                    "true" => token::TokenKind::Literal(core_ir::Literal::Bool(true)),
+                    "false" => token::TokenKind::Literal(core_ir::Literal::Bool(false)),
+                    _ => token::TokenKind::Identifier(text),
+                };
+                Some(Ok(token::Token::new(
                     self.file.clone(),
                     start,
                     self.cursor,
-                    token::TokenKind::Literal(core_ir::Literal::String(
-                        self.source
-                            .clone()
-                            .skip(start + 1)
-                            .take(self.cursor - start - 2)
-                            .collect(),
-                    )),
-                ))
+                    kind,
+                )))
             }
+            Some(c) if is_int_start(c) => Some(self.number(start)),
+            Some('"') => Some(self.string(start)),
             Some(c) => {
                 self.advance();
                 match c {
                     '(' | ')' | '{' | '}' | '[' | ']' | '<' | '>' | ',' | ';' | ':' | '.' | '='
-                    | '|' => Some(token::Token::new(
+                    | '|' | '@' => Some(Ok(token::Token::new(
                         self.file.clone(),
                         start,
                         self.cursor,
                         token::TokenKind::Punctuation(c.to_string()),
-                    )),
+                    ))),
                     '-' => {
                         if self.peek() == Some('>') {
                             self.advance();
-                            Some(token::Token::new(
+                            Some(Ok(token::Token::new(
                                 self.file.clone(),
                                 start,
                                 self.cursor,
                                 token::TokenKind::Punctuation("->".to_string()),
-                            ))
+                            )))
                         } else {
-                            panic!("unexpected character: {}", c);
+                            Some(Err(Error::UnexpectedCharacter {
+                                character: c,
+                                location: self.location(start),
+                            }))
                         }
                     }
-                    _ => panic!("unexpected character: {}", c),
+                    _ => Some(Err(Error::UnexpectedCharacter {
+                        character: c,
+                        location: self.location(start),
+                    })),
                 }
             }
         }
     }
 }
 
-fn is_int_start(c: char) -> bool {
-    c.is_digit(10)
+impl Lexer<'_> {
+    /// Returns true if the source at the cursor starts with `word`
+    /// and `word` is not followed by an identifier character.
+    fn looking_at_word(&self, word: &str) -> bool {
+        let mut rest = self.source.clone();
+        word.chars().all(|c| rest.next() == Some(c))
+            && !rest.next().is_some_and(is_identifier_continue)
+    }
+
+    /// Skips digits of the given radix and `_` separators.
+    /// Returns the digits without separators.
+    fn digits(&mut self, radix: u32) -> String {
+        self.skip_while(|c| c.is_digit(radix) || c == '_')
+            .chars()
+            .filter(|&c| c != '_')
+            .collect()
+    }
+
+    /// Lexes a numeric literal starting at an optional `-`.
+    ///
+    /// Integers may be written in decimal or with a `0x`, `0o` or `0b` prefix.
+    /// Decimal literals with a fraction or an exponent are floats.
+    /// Digits may be separated by `_`.
+    fn number(&mut self, start: usize) -> Result<token::Token> {
+        let sign = if self.peek() == Some('-') {
+            self.advance();
+            "-"
+        } else {
+            ""
+        };
+
+        let radix = match (self.peek(), self.peek_next()) {
+            (Some('0'), Some('x')) => Some(16),
+            (Some('0'), Some('o')) => Some(8),
+            (Some('0'), Some('b')) => Some(2),
+            _ => None,
+        };
+
+        let literal = if let Some(radix) = radix {
+            self.advance();
+            self.advance();
+            let digits_start = self.cursor;
+            let digits = self.digits(radix);
+            // Reject e.g. `0b102` as a whole instead of splitting it into `0b10` and `2`.
+            if self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                self.skip_while(|c| c.is_ascii_alphanumeric());
+                return Err(Error::InvalidDigit {
+                    radix,
+                    location: self.location(digits_start),
+                });
+            }
+            if digits.is_empty() {
+                return Err(Error::MissingDigits {
+                    location: self.location(start),
+                });
+            }
+            let value = i64::from_str_radix(&format!("{sign}{digits}"), radix).map_err(|_| {
+                Error::IntegerOverflow {
+                    location: self.location(start),
+                }
+            })?;
+            core_ir::Literal::Int(value)
+        } else {
+            let mut text = format!("{sign}{}", self.digits(10));
+            let mut is_float = false;
+            if self.peek() == Some('.') {
+                self.advance();
+                text = text + "." + &self.digits(10);
+                is_float = true;
+            }
+            if matches!(self.peek(), Some('e' | 'E')) {
+                self.advance();
+                text.push('e');
+                if let Some(c @ ('+' | '-')) = self.peek() {
+                    self.advance();
+                    text.push(c);
+                }
+                let exponent = self.digits(10);
+                if exponent.is_empty() {
+                    return Err(Error::MissingDigits {
+                        location: self.location(start),
+                    });
+                }
+                text += &exponent;
+                is_float = true;
+            }
+            if is_float {
+                // Digits with an optional fraction and exponent are always a valid f64.
+                core_ir::Literal::Float(text.parse().unwrap())
+            } else {
+                // The only way a sequence of digits fails to parse is overflow.
+                let value = text.parse().map_err(|_| Error::IntegerOverflow {
+                    location: self.location(start),
+                })?;
+                core_ir::Literal::Int(value)
+            }
+        };
+
+        Ok(token::Token::new(
+            self.file.clone(),
+            start,
+            self.cursor,
+            token::TokenKind::Literal(literal),
+        ))
+    }
+
+    /// Lexes a string literal starting at the opening `"`, decoding escape sequences.
+    /// Strings may span multiple lines; a `\` at the end of a line skips the line break
+    /// and the leading whitespace of the next line.
+    /// On an invalid escape, the rest of the literal is still consumed.
+    fn string(&mut self, start: usize) -> Result<token::Token> {
+        self.advance();
+        let mut value = String::new();
+        let mut error = None;
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(Error::UnterminatedString {
+                        location: self.location(start),
+                    })
+                }
+                Some('"') => break,
+                Some('\\') => {
+                    let escape_start = self.cursor;
+                    self.advance();
+                    match self.escape(escape_start) {
+                        Ok(Some(c)) => value.push(c),
+                        Ok(None) => {}
+                        Err(e) => {
+                            error.get_or_insert(e);
+                        }
+                    }
+                }
+                Some(c) => {
+                    self.advance();
+                    value.push(c);
+                }
+            }
+        }
+        self.advance();
+        if let Some(error) = error {
+            return Err(error);
+        }
+        Ok(token::Token::new(
+            self.file.clone(),
+            start,
+            self.cursor,
+            token::TokenKind::Literal(core_ir::Literal::String(value)),
+        ))
+    }
+
+    /// Decodes the escape sequence after a `\`.
+    /// Returns `None` for a line continuation.
+    fn escape(&mut self, start: usize) -> Result<Option<char>> {
+        let Some(c) = self.peek() else {
+            // Reported as an unterminated string by the caller.
+            return Ok(None);
+        };
+        self.advance();
+        let c = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            // A line continuation, with Unix or Windows line endings.
+            '\n' | '\r' if c == '\n' || self.peek() == Some('\n') => {
+                self.skip_while(char::is_whitespace);
+                return Ok(None);
+            }
+            'u' => {
+                if self.peek() != Some('{') {
+                    return Err(Error::InvalidUnicodeEscape {
+                        location: self.location(start),
+                    });
+                }
+                self.advance();
+                let digits = self.skip_while(|c| c.is_ascii_hexdigit());
+                if self.peek() != Some('}') || digits.is_empty() || digits.len() > 6 {
+                    return Err(Error::InvalidUnicodeEscape {
+                        location: self.location(start),
+                    });
+                }
+                self.advance();
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| Error::InvalidUnicodeEscape {
+                        location: self.location(start),
+                    })?
+            }
+            _ => {
+                return Err(Error::InvalidEscape {
+                    location: self.location(start),
+                })
+            }
+        };
+        Ok(Some(c))
+    }
+
+    /// Lexes a raw string literal such as `r"..."` or `r#"..."#` starting at the `r`.
+    /// Raw strings contain no escape sequences and end at a `"` followed by
+    /// as many `#` as the opening delimiter, so they can embed arbitrary text.
+    fn raw_string(&mut self, start: usize) -> Result<token::Token> {
+        self.advance();
+        let hashes = self.skip_while(|c| c == '#').len();
+        if self.peek() != Some('"') {
+            return Err(Error::UnterminatedString {
+                location: self.location(start),
+            });
+        }
+        self.advance();
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(Error::UnterminatedString {
+                        location: self.location(start),
+                    })
+                }
+                Some('"') => {
+                    self.advance();
+                    let closing = self.source.clone().take(hashes);
+                    if closing.clone().count() == hashes && closing.clone().all(|c| c == '#') {
+                        (0..hashes).for_each(|_| self.advance());
+                        break;
+                    }
+                    value.push('"');
+                }
+                Some(c) => {
+                    self.advance();
+                    value.push(c);
+                }
+            }
+        }
+        Ok(token::Token::new(
+            self.file.clone(),
+            start,
+            self.cursor,
+            token::TokenKind::Literal(core_ir::Literal::String(value)),
+        ))
+    }
 }
 
-fn is_int_continue(c: char) -> bool {
-    c.is_digit(10)
+impl Iterator for Lexer<'_> {
+    type Item = Result<token::Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+fn is_int_start(c: char) -> bool {
+    c.is_ascii_digit()
 }
 
 fn is_identifier_start(c: char) -> bool {
@@ -181,3 +544,165 @@ fn is_identifier_start(c: char) -> bool {
 fn is_identifier_continue(c: char) -> bool {
     c.is_alphanumeric() || c == '_'
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<Result<token::Token>> {
+        Lexer::new("test.coco".to_string(), source).collect()
+    }
+
+    /// Lexes a source consisting of a single literal.
+    fn literal(source: &str) -> core_ir::Literal {
+        match &mut lex(source)[..] {
+            [Ok(token::Token {
+                kind: token::TokenKind::Literal(literal),
+                ..
+            })] => std::mem::replace(literal, core_ir::Literal::Bool(false)),
+            tokens => panic!("expected a single literal, got {:?}", tokens),
+        }
+    }
+
+    /// Lexes a string literal and returns its value.
+    fn string(source: &str) -> String {
+        match literal(source) {
+            core_ir::Literal::String(value) => value,
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unexpected_character() {
+        let tokens = lex("x $ y");
+        assert_eq!(tokens.len(), 3);
+        let Err(Error::UnexpectedCharacter {
+            character,
+            location,
+        }) = &tokens[1]
+        else {
+            panic!("expected an unexpected character, got {:?}", tokens[1]);
+        };
+        assert_eq!(*character, '$');
+        assert_eq!((location.start, location.end), (2, 3));
+        assert!(matches!(&tokens[2], Ok(token) if token.is_identifier()));
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let tokens = lex("x \"abc");
+        let Some(Err(Error::UnterminatedString { location })) = tokens.last() else {
+            panic!("expected an unterminated string, got {:?}", tokens);
+        };
+        assert_eq!((location.start, location.end), (2, 6));
+    }
+
+    #[test]
+    fn integer_overflow() {
+        let tokens = lex("9223372036854775808");
+        assert!(matches!(&tokens[..], [Err(Error::IntegerOverflow { .. })]));
+    }
+
+    #[test]
+    fn comments_are_trivia() {
+        let mut lexer = Lexer::new("test.coco".to_string(), "x // é\ny /* z */");
+        let tokens: Vec<_> = lexer.by_ref().collect();
+        assert_eq!(tokens.len(), 2);
+        let Ok(y) = &tokens[1] else {
+            panic!("expected a token, got {:?}", tokens[1]);
+        };
+        assert_eq!((y.location.start, y.location.end), (7, 8));
+        let comments: Vec<_> = lexer
+            .trivia
+            .iter()
+            .map(|comment| (comment.kind, comment.text.as_str()))
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                (token::CommentKind::Line, "// é"),
+                (token::CommentKind::Block, "/* z */")
+            ]
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(string(r#""a\n\t\r\0\\\"\'""#), "a\n\t\r\0\\\"'");
+        assert_eq!(string(r#""\u{48}\u{1F600}""#), "H\u{1F600}");
+        assert_eq!(string("\"one \\\n    two\""), "one two");
+        assert_eq!(string("\"a\\\r\n  b\""), "ab");
+        assert_eq!(string("\"two\nlines\""), "two\nlines");
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        let tokens = lex(r#"x "a\qb""#);
+        let Some(Err(Error::InvalidEscape { location })) = tokens.get(1) else {
+            panic!("expected an invalid escape, got {:?}", tokens);
+        };
+        assert_eq!((location.start, location.end), (4, 6));
+        for source in [
+            r#""\u{}""#,
+            r#""\u{1234567}""#,
+            r#""\u{D800}""#,
+            r#""\u48""#,
+        ] {
+            assert!(
+                matches!(
+                    &lex(source)[..],
+                    [Err(Error::InvalidUnicodeEscape { .. }), ..]
+                ),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(string(r#"r"a\n""#), "a\\n");
+        assert_eq!(string(r###"r##"say "#hi"#"##"###), "say \"#hi\"#");
+        assert_eq!(string("r\"two\nlines\""), "two\nlines");
+        assert!(matches!(
+            &lex(r##"r#"open""##)[..],
+            [Err(Error::UnterminatedString { .. })]
+        ));
+    }
+
+    #[test]
+    fn numeric_literals() {
+        use core_ir::Literal::{Float, Int};
+        let int = |source| match literal(source) {
+            Int(value) => value,
+            other => panic!("expected an integer, got {:?}", other),
+        };
+        let float = |source| match literal(source) {
+            Float(value) => value,
+            other => panic!("expected a float, got {:?}", other),
+        };
+        assert_eq!(int("-1"), -1);
+        assert_eq!(int("1_000_000"), 1_000_000);
+        assert_eq!(int("0xFF_FF"), 0xFFFF);
+        assert_eq!(int("0b1010"), 10);
+        assert_eq!(int("0o17"), 15);
+        assert_eq!(int("-9223372036854775808"), i64::MIN);
+        assert_eq!(float("1.5e3"), 1500.0);
+        assert_eq!(float("-2.5E-1"), -0.25);
+        assert_eq!(float("-inf"), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn invalid_numeric_literals() {
+        assert!(matches!(&lex("0x")[..], [Err(Error::MissingDigits { .. })]));
+        assert!(matches!(&lex("1e")[..], [Err(Error::MissingDigits { .. })]));
+        assert!(matches!(
+            &lex("0b102")[..],
+            [Err(Error::InvalidDigit { radix: 2, .. })]
+        ));
+        assert!(matches!(
+            &lex("0x1_0000_0000_0000_0000")[..],
+            [Err(Error::IntegerOverflow { .. })]
+        ));
+    }
+}
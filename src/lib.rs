@@ -0,0 +1,19 @@
+pub mod arity;
+pub mod attribute;
+pub mod core_ir;
+pub mod debugger;
+pub mod exhaustive;
+pub mod focus;
+pub mod lexer;
+pub mod location;
+pub mod machine;
+pub mod name;
+pub mod parser;
+pub mod prim;
+pub mod printer;
+pub mod resolve;
+pub mod session;
+pub mod suggest;
+pub mod token;
+pub mod typecheck;
+pub mod visit;
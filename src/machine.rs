@@ -0,0 +1,1404 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::attribute;
+use crate::core_ir::*;
+use crate::exhaustive;
+use crate::location;
+use crate::name::Name;
+use crate::prim;
+use crate::printer;
+use crate::visit::{self, Visitor};
+use ariadne::Label;
+use ariadne::Report;
+use ariadne::ReportKind;
+use thiserror::Error;
+
+/// How a `do` cut against a `then` is reduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Call-by-value: run the `do` first, with the `then` as its continuation.
+    #[default]
+    Value,
+    /// Call-by-name: bind the variable of the `then` to the `do`,
+    /// which runs whenever the variable is cut against anything but a `then`.
+    Name,
+    /// Call-by-need: like `Name`, but the `do` runs at most once and its value is shared.
+    Need,
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cbv" => Ok(Strategy::Value),
+            "cbn" => Ok(Strategy::Name),
+            "need" => Ok(Strategy::Need),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Value => write!(f, "cbv"),
+            Strategy::Name => write!(f, "cbn"),
+            Strategy::Need => write!(f, "need"),
+        }
+    }
+}
+
+/// A producer whose variables are bound by the environment it closes over.
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    Literal(Literal),
+    Construct(Rc<Applied<'a>>),
+    Comatch(&'a Comatch<Name>, Env<'a>),
+    /// A `do` delayed by `Strategy::Name` or `Strategy::Need`.
+    Thunk(Rc<Thunk<'a>>),
+}
+
+#[derive(Debug)]
+pub struct Thunk<'a> {
+    pub delayed: &'a Do<Name>,
+    pub env: Env<'a>,
+    /// Whether its value is kept once it has run, under `Strategy::Need`.
+    pub shared: bool,
+    pub value: RefCell<Option<Value<'a>>>,
+    _allocation: Allocation,
+}
+
+/// A consumer whose variables are bound by the environment it closes over.
+#[derive(Debug, Clone)]
+pub enum Covalue<'a> {
+    Finish,
+    Then(&'a Then<Name>, Env<'a>),
+    Destruct(Rc<Applied<'a>>),
+    Match(&'a Match<Name>, Env<'a>),
+    /// Keeps the value passed to it in a shared thunk before passing it on.
+    Update(Rc<Thunk<'a>>, Box<Covalue<'a>>),
+    /// Waits for the value of a thunk that a `prim` or `switch` needs,
+    /// holding the values of its producers before that thunk.
+    Resume(&'a Statement<Name>, Env<'a>, Vec<Value<'a>>),
+}
+
+impl<'a> Covalue<'a> {
+    /// Whether both are the same covalue, rather than equal ones.
+    fn same(&self, other: &Covalue<'a>) -> bool {
+        match (self, other) {
+            (Covalue::Finish, Covalue::Finish) => true,
+            (Covalue::Then(this, env), Covalue::Then(other, other_env)) => {
+                std::ptr::eq(*this, *other) && env.same(other_env)
+            }
+            (Covalue::Destruct(this), Covalue::Destruct(other)) => Rc::ptr_eq(this, other),
+            (Covalue::Match(this, env), Covalue::Match(other, other_env)) => {
+                std::ptr::eq(*this, *other) && env.same(other_env)
+            }
+            (Covalue::Update(this, covalue), Covalue::Update(other, other_covalue)) => {
+                Rc::ptr_eq(this, other) && covalue.same(other_covalue)
+            }
+            (
+                Covalue::Resume(this, env, values),
+                Covalue::Resume(other, other_env, other_values),
+            ) => {
+                std::ptr::eq(*this, *other)
+                    && env.same(other_env)
+                    && values.len() == other_values.len()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A constructor or destructor applied to its arguments.
+#[derive(Debug)]
+pub struct Applied<'a> {
+    pub tag: &'a str,
+    pub values: Vec<Value<'a>>,
+    pub covalues: Vec<Covalue<'a>>,
+    _allocation: Allocation,
+}
+
+/// Something left to print, or to drop.
+/// Values can be nested deeper than the stack allows, so both use a worklist of these.
+enum Part<'a> {
+    Text(&'a str),
+    Value(Value<'a>),
+    Covalue(Covalue<'a>),
+    Env(Env<'a>),
+}
+
+/// Prints the parts from the last one pushed on.
+fn write_parts(f: &mut fmt::Formatter, mut parts: Vec<Part>) -> fmt::Result {
+    while let Some(part) = parts.pop() {
+        match part {
+            Part::Text(text) => write!(f, "{}", text)?,
+            Part::Value(Value::Literal(literal)) => write!(f, "{}", printer::literal(&literal))?,
+            Part::Value(Value::Construct(applied)) | Part::Covalue(Covalue::Destruct(applied)) => {
+                applied.push_parts(&mut parts)
+            }
+            Part::Value(Value::Comatch(..)) => write!(f, "comatch {{ … }}")?,
+            Part::Value(Value::Thunk(thunk)) => match &*thunk.value.borrow() {
+                Some(value) => parts.push(Part::Value(value.clone())),
+                None => write!(f, "do {} …", thunk.delayed.binder.name.text)?,
+            },
+            Part::Covalue(Covalue::Finish) => write!(f, "finish")?,
+            Part::Covalue(Covalue::Then(Then { binder, .. }, _)) => {
+                write!(f, "then {} …", binder.name.text)?
+            }
+            Part::Covalue(Covalue::Match(..)) => write!(f, "match {{ … }}")?,
+            Part::Covalue(Covalue::Update(_, covalue)) => parts.push(Part::Covalue(*covalue)),
+            Part::Covalue(Covalue::Resume(..)) => write!(f, "resume …")?,
+            Part::Env(_) => unreachable!("environments are only dropped"),
+        }
+    }
+    Ok(())
+}
+
+/// Drops the parts, taking apart the applications, thunks and frames nothing else holds,
+/// so that their own parts are dropped by this loop rather than recursively.
+fn drop_parts(mut parts: Vec<Part>) {
+    while let Some(part) = parts.pop() {
+        match part {
+            Part::Text(_) | Part::Value(Value::Literal(_)) | Part::Covalue(Covalue::Finish) => {}
+            Part::Value(Value::Construct(applied)) | Part::Covalue(Covalue::Destruct(applied)) => {
+                if let Ok(mut applied) = Rc::try_unwrap(applied) {
+                    applied.take_parts(&mut parts);
+                }
+            }
+            Part::Value(Value::Thunk(thunk)) => {
+                if let Ok(mut thunk) = Rc::try_unwrap(thunk) {
+                    thunk.take_parts(&mut parts);
+                }
+            }
+            Part::Covalue(Covalue::Update(thunk, covalue)) => {
+                parts.push(Part::Value(Value::Thunk(thunk)));
+                parts.push(Part::Covalue(*covalue));
+            }
+            Part::Value(Value::Comatch(_, env))
+            | Part::Covalue(Covalue::Then(_, env))
+            | Part::Covalue(Covalue::Match(_, env)) => parts.push(Part::Env(env)),
+            Part::Covalue(Covalue::Resume(_, env, values)) => {
+                parts.push(Part::Env(env));
+                parts.extend(values.into_iter().map(Part::Value));
+            }
+            Part::Env(Env(Some(frame))) => {
+                if let Ok(mut frame) = Rc::try_unwrap(frame) {
+                    frame.take_parts(&mut parts);
+                }
+            }
+            Part::Env(Env(None)) => {}
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_parts(f, vec![Part::Value(self.clone())])
+    }
+}
+
+impl fmt::Display for Covalue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_parts(f, vec![Part::Covalue(self.clone())])
+    }
+}
+
+impl<'a> Applied<'a> {
+    /// Pushes the parts of `Tag(v1, v2; c1)`, as the printer prints it, last first.
+    fn push_parts(&self, parts: &mut Vec<Part<'a>>) {
+        let mut pieces = vec![Part::Text(self.tag), Part::Text("(")];
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                pieces.push(Part::Text(", "));
+            }
+            pieces.push(Part::Value(value.clone()));
+        }
+        pieces.push(Part::Text(";"));
+        for (i, covalue) in self.covalues.iter().enumerate() {
+            pieces.push(Part::Text(if i > 0 { ", " } else { " " }));
+            pieces.push(Part::Covalue(covalue.clone()));
+        }
+        pieces.push(Part::Text(")"));
+        parts.extend(pieces.into_iter().rev());
+    }
+
+    fn take_parts(&mut self, parts: &mut Vec<Part<'a>>) {
+        parts.extend(self.values.drain(..).map(Part::Value));
+        parts.extend(self.covalues.drain(..).map(Part::Covalue));
+    }
+}
+
+/// Prints `Tag(v1, v2; c1)` like the printer does.
+impl fmt::Display for Applied<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        self.push_parts(&mut parts);
+        write_parts(f, parts)
+    }
+}
+
+impl Drop for Applied<'_> {
+    fn drop(&mut self) {
+        let mut parts = Vec::new();
+        self.take_parts(&mut parts);
+        drop_parts(parts);
+    }
+}
+
+impl<'a> Thunk<'a> {
+    fn take_parts(&mut self, parts: &mut Vec<Part<'a>>) {
+        parts.push(Part::Env(std::mem::take(&mut self.env)));
+        parts.extend(self.value.take().map(Part::Value));
+    }
+}
+
+impl Drop for Thunk<'_> {
+    fn drop(&mut self) {
+        let mut parts = Vec::new();
+        self.take_parts(&mut parts);
+        drop_parts(parts);
+    }
+}
+
+/// Counts the bindings, applications and thunks alive, for `Limits::heap`.
+#[derive(Debug, Clone, Default)]
+struct Heap(Rc<Cell<usize>>);
+
+/// Counts as one on the heap while alive.
+#[derive(Debug)]
+struct Allocation(Heap);
+
+impl Heap {
+    fn allocate(&self) -> Allocation {
+        self.0.set(self.0.get() + 1);
+        Allocation(self.clone())
+    }
+
+    fn size(&self) -> usize {
+        self.0.get()
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        let size = &(self.0).0;
+        size.set(size.get() - 1);
+    }
+}
+
+/// What a variable or covariable is bound to.
+#[derive(Debug, Clone)]
+pub enum Binding<'a> {
+    Value(Value<'a>),
+    Covalue(Covalue<'a>),
+}
+
+/// Bindings of variables and covariables.
+/// Environments are immutable linked lists, so closures share their tails.
+#[derive(Clone, Default)]
+pub struct Env<'a>(Option<Rc<Frame<'a>>>);
+
+struct Frame<'a> {
+    name: &'a Name,
+    binding: Binding<'a>,
+    next: Env<'a>,
+    /// The number of bindings, this one included.
+    depth: usize,
+    _allocation: Allocation,
+}
+
+impl<'a> Frame<'a> {
+    fn take_parts(&mut self, parts: &mut Vec<Part<'a>>) {
+        parts.push(Part::Env(std::mem::take(&mut self.next)));
+        match std::mem::replace(&mut self.binding, Binding::Covalue(Covalue::Finish)) {
+            Binding::Value(value) => parts.push(Part::Value(value)),
+            Binding::Covalue(covalue) => parts.push(Part::Covalue(covalue)),
+        }
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        let mut parts = Vec::new();
+        self.take_parts(&mut parts);
+        drop_parts(parts);
+    }
+}
+
+impl<'a> Env<'a> {
+    fn bind(&self, heap: &Heap, name: &'a Name, binding: Binding<'a>) -> Self {
+        Env(Some(Rc::new(Frame {
+            name,
+            binding,
+            next: self.clone(),
+            depth: self.depth() + 1,
+            _allocation: heap.allocate(),
+        })))
+    }
+
+    fn depth(&self) -> usize {
+        self.0.as_ref().map_or(0, |frame| frame.depth)
+    }
+
+    /// Returns the bindings from the innermost outwards.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a Name, &Binding<'a>)> {
+        std::iter::successors(self.0.as_deref(), |frame| frame.next.0.as_deref())
+            .map(|frame| (frame.name, &frame.binding))
+    }
+
+    fn frames(&self) -> impl Iterator<Item = &Rc<Frame<'a>>> {
+        std::iter::successors(self.0.as_ref(), |frame| frame.next.0.as_ref())
+    }
+
+    /// Returns the innermost binding of a covariable named `text`.
+    fn covariable(&self, text: &str) -> Option<&Rc<Frame<'a>>> {
+        self.frames()
+            .find(|frame| frame.name.text == text && matches!(frame.binding, Binding::Covalue(_)))
+    }
+
+    fn same(&self, other: &Env<'a>) -> bool {
+        match (&self.0, &other.0) {
+            (None, None) => true,
+            (Some(this), Some(other)) => Rc::ptr_eq(this, other),
+            _ => false,
+        }
+    }
+
+    fn lookup(&self, name: &Name) -> &Binding<'a> {
+        self.iter()
+            .find(|(bound, _)| *bound == name)
+            .map(|(_, binding)| binding)
+            .unwrap_or_else(|| unreachable!("`{}` is bound, as names are resolved", name))
+    }
+
+    fn value(&self, name: &Name) -> Value<'a> {
+        match self.lookup(name) {
+            Binding::Value(value) => value.clone(),
+            Binding::Covalue(_) => unreachable!("`{}` is a variable, as names are resolved", name),
+        }
+    }
+
+    fn covalue(&self, name: &Name) -> Covalue<'a> {
+        match self.lookup(name) {
+            Binding::Covalue(covalue) => covalue.clone(),
+            Binding::Value(_) => {
+                unreachable!("`{}` is a covariable, as names are resolved", name)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Env<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Bounds on the resources a run may use. `None` is unbounded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// The number of reductions.
+    pub steps: Option<usize>,
+    /// The number of bindings, constructor and destructor applications
+    /// and delayed `do`s alive at once.
+    pub heap: Option<usize>,
+    /// The number of bindings in the environment of a statement.
+    pub depth: Option<usize>,
+}
+
+/// A resource bounded by `Limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Steps,
+    Heap,
+    Depth,
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resource::Steps => write!(f, "step"),
+            Resource::Heap => write!(f, "heap"),
+            Resource::Depth => write!(f, "environment depth"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("primitive `{name}` failed: {message}")]
+    Primitive {
+        name: String,
+        message: String,
+        location: location::Location,
+    },
+    #[error("no branch matches `{value}`")]
+    NoBranch {
+        value: String,
+        location: location::Location,
+    },
+    #[error("cannot cut `{producer}` against `{consumer}`")]
+    Stuck {
+        producer: String,
+        consumer: String,
+        location: location::Location,
+    },
+    #[error("resource exhausted: {resource} limit of {limit} reached")]
+    Exhausted {
+        resource: Resource,
+        limit: usize,
+        location: location::Location,
+    },
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        let (location, label) = match error {
+            Error::Primitive { location, .. } => (location, "while running this"),
+            Error::NoBranch { location, .. } => (location, "in this switch"),
+            Error::Stuck { location, .. } => (location, "while running this"),
+            Error::Exhausted { location, .. } => (location, "while running this"),
+        };
+        Report::build(ReportKind::Error, location.clone())
+            .with_message(message)
+            .with_label(Label::new(location).with_message(label))
+            .finish()
+    }
+}
+
+/// What the machine does next.
+#[derive(Debug, Clone)]
+pub enum Control<'a> {
+    /// Run a statement in an environment.
+    Statement(&'a Statement<Name>, Env<'a>),
+    /// Reduce a value against a covalue.
+    Cut(Value<'a>, Covalue<'a>),
+    /// A value reached `finish`.
+    Finished(Value<'a>),
+}
+
+/// Describes the reduction the machine performs next.
+impl fmt::Display for Control<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Control::Statement(statement, _) => match &statement.kind {
+                StatementKind::Cut(Cut { producer, .. }) => match &producer.kind {
+                    ProducerKind::Do(Do { binder, .. }) => write!(f, "do {} …", binder.name.text),
+                    _ => write!(f, "cut"),
+                },
+                StatementKind::Prim(Prim { name, .. }) => write!(f, "prim[{}]", name),
+                StatementKind::Switch(_) => write!(f, "switch"),
+                StatementKind::Invoke(Invoke { name, .. }) => write!(f, "invoke[{}]", name.text),
+                StatementKind::Error => write!(f, "<error>"),
+            },
+            Control::Cut(value, covalue) => write!(f, "{} | {}", value, covalue),
+            Control::Finished(value) => write!(f, "finished with {}", value),
+        }
+    }
+}
+
+/// The states a machine has been in, so that it can go back to them.
+/// States share their environments, so keeping them all is cheap.
+#[derive(Default)]
+struct History<'a> {
+    /// The state after each number of reductions.
+    states: Vec<(Control<'a>, &'a location::Location)>,
+    /// The thunks given a value by a reduction, with its number and that value.
+    updates: Vec<(usize, Rc<Thunk<'a>>, Value<'a>)>,
+    /// The impure primitives run by a reduction, with its number.
+    effects: Vec<(usize, &'a str)>,
+}
+
+/// An abstract machine running a focused program (see `focus::Focuser`),
+/// one reduction at a time.
+pub struct Machine<'a> {
+    definitions: HashMap<&'a Name, &'a Definition<Name>>,
+    primitives: &'a prim::Registry,
+    strategy: Strategy,
+    /// Strategies of the `do`s in definitions with a `@strategy` attribute.
+    /// Nodes are identified by address, as the program outlives the machine.
+    strategies: HashMap<*const Do<Name>, Strategy>,
+    control: Control<'a>,
+    /// The statement run last, for error reports.
+    location: &'a location::Location,
+    /// The number of reductions performed so far.
+    steps: usize,
+    history: Option<History<'a>>,
+    /// The impure primitive run by the reduction being performed, if any.
+    effect: Cell<Option<&'a str>>,
+    limits: Limits,
+    heap: Heap,
+}
+
+impl<'a> Machine<'a> {
+    /// Prepares to run `entry`, which takes no producers,
+    /// with `finish` for each of its consumers.
+    /// `strategy` applies to definitions without a `@strategy` attribute.
+    pub fn new(
+        program: &'a Program<Name>,
+        primitives: &'a prim::Registry,
+        entry: &'a Definition<Name>,
+        strategy: Strategy,
+    ) -> Self {
+        let mut strategies = HashMap::new();
+        for definition in &program.definitions {
+            if let Some(strategy) = attribute::strategy(definition) {
+                Strategies {
+                    strategy,
+                    strategies: &mut strategies,
+                }
+                .statement(&definition.body);
+            }
+        }
+        let heap = Heap::default();
+        let env = entry.returns.iter().fold(Env::default(), |env, binder| {
+            env.bind(&heap, &binder.name, Binding::Covalue(Covalue::Finish))
+        });
+        Self {
+            definitions: program
+                .definitions
+                .iter()
+                .map(|definition| (&definition.name, definition))
+                .collect(),
+            primitives,
+            strategy,
+            strategies,
+            control: Control::Statement(&entry.body, env),
+            location: &entry.body.location,
+            steps: 0,
+            history: None,
+            effect: Cell::new(None),
+            limits: Limits::default(),
+            heap,
+        }
+    }
+
+    /// Fails with `Error::Exhausted` once a reduction goes beyond `limits`.
+    pub fn limit(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Keeps every state from now on, so that `go_to` can return to it.
+    /// Call before the first reduction.
+    pub fn record(&mut self) {
+        debug_assert_eq!(self.steps, 0, "recording starts with the first reduction");
+        self.history = Some(History {
+            states: vec![(self.control.clone(), self.location)],
+            updates: Vec::new(),
+            effects: Vec::new(),
+        });
+    }
+
+    /// Goes back or forward to the state after `steps` reductions,
+    /// if it has been recorded. Thunks get the values they had then.
+    pub fn go_to(&mut self, steps: usize) -> bool {
+        let Some(history) = &self.history else {
+            return false;
+        };
+        let Some((control, location)) = history.states.get(steps) else {
+            return false;
+        };
+        for (step, thunk, value) in &history.updates {
+            *thunk.value.borrow_mut() = (*step < steps).then(|| value.clone());
+        }
+        self.control = control.clone();
+        self.location = location;
+        self.steps = steps;
+        true
+    }
+
+    /// Names the impure primitives run by the recorded reductions numbered `steps`,
+    /// whose effects going back does not undo.
+    pub fn effects(&self, steps: std::ops::Range<usize>) -> Vec<&'a str> {
+        let mut names = Vec::new();
+        for (step, name) in self.history.iter().flat_map(|history| &history.effects) {
+            if steps.contains(step) && !names.contains(name) {
+                names.push(*name);
+            }
+        }
+        names
+    }
+
+    /// Returns the number of the recorded reduction that bound the covariable
+    /// named `text` in scope, or 0 if it was bound before the first one.
+    pub fn bound(&self, text: &str) -> Option<usize> {
+        let Control::Statement(_, env) = &self.control else {
+            return None;
+        };
+        let frame = env.covariable(text)?;
+        let states = &self.history.as_ref()?.states;
+        let first = states.iter().position(|(control, _)| {
+            matches!(control, Control::Statement(_, env) if env.frames().any(|other| Rc::ptr_eq(frame, other)))
+        })?;
+        Some(first.saturating_sub(1))
+    }
+
+    /// Returns what the covariable named `text` in scope is bound to.
+    pub fn covariable(&self, text: &str) -> Option<Covalue<'a>> {
+        let Control::Statement(_, env) = &self.control else {
+            return None;
+        };
+        match &env.covariable(text)?.binding {
+            Binding::Covalue(covalue) => Some(covalue.clone()),
+            Binding::Value(_) => None,
+        }
+    }
+
+    /// Whether the next reduction passes a value to `covalue`.
+    pub fn invokes(&self, covalue: &Covalue<'a>) -> bool {
+        match &self.control {
+            // A thunk is run first, unless it is passed to a `then`.
+            Control::Cut(Value::Thunk(_), next) if !matches!(next, Covalue::Then(..)) => false,
+            Control::Cut(_, next) => next.same(covalue),
+            _ => false,
+        }
+    }
+
+    /// Replaces the current state without a reduction.
+    fn replace(&mut self, control: Control<'a>) {
+        if let Some(history) = &mut self.history {
+            history.states[self.steps].0 = control.clone();
+        }
+        self.control = control;
+    }
+
+    pub fn control(&self) -> &Control<'a> {
+        &self.control
+    }
+
+    /// Where the statement run last is.
+    pub fn location(&self) -> &'a location::Location {
+        self.location
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Returns the definition that `statement` is the body of, if any.
+    pub fn definition_of(&self, statement: &Statement<Name>) -> Option<&'a Definition<Name>> {
+        self.definitions
+            .values()
+            .find(|definition| std::ptr::eq(&definition.body, statement))
+            .copied()
+    }
+
+    /// Runs until a value reaches `finish`,
+    /// then runs the thunks in that value so that it can be printed.
+    pub fn run(&mut self) -> Result<Value<'a>, Error> {
+        self.run_traced(|_| {})
+    }
+
+    /// Runs as `run` does, calling `trace` with the machine before each reduction,
+    /// including those that run the thunks in the final value, and once finished.
+    pub fn run_traced(&mut self, mut trace: impl FnMut(&Self)) -> Result<Value<'a>, Error> {
+        let value = self.finish(&mut trace)?;
+        let value = self.force(value, &mut trace)?;
+        self.replace(Control::Finished(value.clone()));
+        Ok(value)
+    }
+
+    fn finish(&mut self, trace: &mut impl FnMut(&Self)) -> Result<Value<'a>, Error> {
+        loop {
+            trace(self);
+            if let Control::Finished(value) = &self.control {
+                return Ok(value.clone());
+            }
+            self.step()?;
+        }
+    }
+
+    /// Replaces the thunks in a value, including those in its constructor arguments,
+    /// by their values.
+    fn force(
+        &mut self,
+        mut value: Value<'a>,
+        trace: &mut impl FnMut(&Self),
+    ) -> Result<Value<'a>, Error> {
+        // The applications whose arguments are being forced, with those forced so far.
+        let mut pending: Vec<(Rc<Applied<'a>>, Vec<Value<'a>>)> = Vec::new();
+        loop {
+            match value {
+                Value::Thunk(_) => {
+                    self.replace(Control::Cut(value, Covalue::Finish));
+                    value = self.finish(trace)?;
+                    continue;
+                }
+                Value::Construct(applied) if !applied.values.is_empty() => {
+                    value = applied.values[0].clone();
+                    pending.push((applied, Vec::new()));
+                    continue;
+                }
+                _ => {}
+            }
+            // `value` is forced, so it is the next argument of the innermost application.
+            loop {
+                let Some((applied, mut values)) = pending.pop() else {
+                    return Ok(value);
+                };
+                values.push(value);
+                if let Some(next) = applied.values.get(values.len()) {
+                    value = next.clone();
+                    pending.push((applied, values));
+                    break;
+                }
+                value = Value::Construct(Rc::new(Applied {
+                    tag: applied.tag,
+                    values,
+                    covalues: applied.covalues.clone(),
+                    _allocation: self.heap.allocate(),
+                }));
+            }
+        }
+    }
+
+    /// Performs one reduction. Does nothing once finished.
+    /// Reductions that have been recorded are replayed rather than performed again,
+    /// so primitives do not repeat their effects.
+    pub fn step(&mut self) -> Result<(), Error> {
+        if self.go_to(self.steps + 1) {
+            return Ok(());
+        }
+        if let Some(limit) = self.limits.steps {
+            if self.steps >= limit && !matches!(self.control, Control::Finished(_)) {
+                let location = match &self.control {
+                    Control::Statement(statement, _) => &statement.location,
+                    Control::Cut(..) | Control::Finished(_) => self.location,
+                };
+                return Err(exhausted(Resource::Steps, limit, location));
+            }
+        }
+        let updated = match &self.control {
+            Control::Cut(_, Covalue::Update(thunk, _)) if thunk.value.borrow().is_none() => {
+                Some(thunk.clone())
+            }
+            _ => None,
+        };
+        self.control = match &self.control {
+            Control::Statement(statement, env) => {
+                self.location = &statement.location;
+                self.statement(statement, env)?
+            }
+            Control::Cut(value, covalue) => self.cut(value.clone(), covalue.clone())?,
+            Control::Finished(_) => return Ok(()),
+        };
+        let effect = self.effect.take();
+        if let Some(history) = &mut self.history {
+            if let Some(name) = effect {
+                history.effects.push((self.steps, name));
+            }
+            if let Some(thunk) = updated {
+                if let Some(value) = thunk.value.borrow().clone() {
+                    history.updates.push((self.steps, thunk.clone(), value));
+                }
+            }
+            history.states.push((self.control.clone(), self.location));
+        }
+        self.steps += 1;
+        // The heap and the environment grew during the step just taken,
+        // so these limits are reported at the statement that step ran.
+        if let Some(limit) = self.limits.heap {
+            if self.heap.size() > limit {
+                return Err(exhausted(Resource::Heap, limit, self.location));
+            }
+        }
+        if let (Some(limit), Control::Statement(_, env)) = (self.limits.depth, &self.control) {
+            if env.depth() > limit {
+                return Err(exhausted(Resource::Depth, limit, self.location));
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(
+        &self,
+        statement: &'a Statement<Name>,
+        env: &Env<'a>,
+    ) -> Result<Control<'a>, Error> {
+        match &statement.kind {
+            StatementKind::Cut(Cut { producer, consumer }) => match &producer.kind {
+                ProducerKind::Do(delayed) => {
+                    let strategy = self
+                        .strategies
+                        .get(&(delayed as *const _))
+                        .copied()
+                        .unwrap_or(self.strategy);
+                    match (strategy, self.covalue(consumer, env)) {
+                        (Strategy::Name | Strategy::Need, Covalue::Then(then, scope)) => {
+                            let thunk = Thunk {
+                                delayed,
+                                env: env.clone(),
+                                shared: strategy == Strategy::Need,
+                                value: RefCell::new(None),
+                                _allocation: self.heap.allocate(),
+                            };
+                            Ok(Control::Statement(
+                                &then.body,
+                                scope.bind(
+                                    &self.heap,
+                                    &then.binder.name,
+                                    Binding::Value(Value::Thunk(Rc::new(thunk))),
+                                ),
+                            ))
+                        }
+                        (_, covalue) => Ok(Control::Statement(
+                            &delayed.body,
+                            env.bind(&self.heap, &delayed.binder.name, Binding::Covalue(covalue)),
+                        )),
+                    }
+                }
+                _ => Ok(Control::Cut(
+                    self.value(producer, env),
+                    self.covalue(consumer, env),
+                )),
+            },
+            StatementKind::Prim(_) | StatementKind::Switch(_) => {
+                self.strict(statement, env, Vec::new())
+            }
+            StatementKind::Invoke(Invoke {
+                name,
+                producers,
+                consumers,
+            }) => {
+                let definition = self.definitions[name];
+                let mut callee = Env::default();
+                for (binder, producer) in definition.parameters.iter().zip(producers) {
+                    callee = callee.bind(
+                        &self.heap,
+                        &binder.name,
+                        Binding::Value(self.value(producer, env)),
+                    );
+                }
+                for (binder, consumer) in definition.returns.iter().zip(consumers) {
+                    callee = callee.bind(
+                        &self.heap,
+                        &binder.name,
+                        Binding::Covalue(self.covalue(consumer, env)),
+                    );
+                }
+                Ok(Control::Statement(&definition.body, callee))
+            }
+            StatementKind::Error => unreachable!("programs with errors are not run"),
+        }
+    }
+
+    fn cut(&self, value: Value<'a>, covalue: Covalue<'a>) -> Result<Control<'a>, Error> {
+        match (&value, &covalue) {
+            (_, Covalue::Then(Then { binder, body }, env)) => {
+                return Ok(Control::Statement(
+                    body,
+                    env.bind(&self.heap, &binder.name, Binding::Value(value.clone())),
+                ))
+            }
+            // Anything but a `then` needs the value of a thunk.
+            (Value::Thunk(thunk), _) => {
+                let value = thunk.value.borrow().clone();
+                return Ok(match value {
+                    Some(value) => Control::Cut(value, covalue),
+                    None => {
+                        let covalue = if thunk.shared {
+                            Covalue::Update(thunk.clone(), Box::new(covalue))
+                        } else {
+                            covalue
+                        };
+                        Control::Statement(
+                            &thunk.delayed.body,
+                            thunk.env.bind(
+                                &self.heap,
+                                &thunk.delayed.binder.name,
+                                Binding::Covalue(covalue),
+                            ),
+                        )
+                    }
+                });
+            }
+            (_, Covalue::Finish) => return Ok(Control::Finished(value)),
+            (_, Covalue::Update(thunk, covalue)) => {
+                // A continuation may be invoked more than once; the first value is kept.
+                thunk
+                    .value
+                    .borrow_mut()
+                    .get_or_insert_with(|| value.clone());
+                return Ok(Control::Cut(value, (**covalue).clone()));
+            }
+            (_, Covalue::Resume(statement, env, values)) => {
+                let mut values = values.clone();
+                values.push(value);
+                return self.strict(statement, env, values);
+            }
+            (Value::Construct(applied), Covalue::Match(Match { clauses }, env)) => {
+                if let Some(Clause { pattern, body, .. }) = clauses
+                    .iter()
+                    .find(|clause| clause.pattern.tag == applied.tag)
+                {
+                    let env = bind(
+                        &self.heap,
+                        env,
+                        &pattern.parameters,
+                        &pattern.returns,
+                        applied,
+                    );
+                    return Ok(Control::Statement(body, env));
+                }
+            }
+            (Value::Comatch(Comatch { clauses }, env), Covalue::Destruct(applied)) => {
+                if let Some(Coclause {
+                    copattern, body, ..
+                }) = clauses
+                    .iter()
+                    .find(|clause| clause.copattern.tag == applied.tag)
+                {
+                    let env = bind(
+                        &self.heap,
+                        env,
+                        &copattern.parameters,
+                        &copattern.returns,
+                        applied,
+                    );
+                    return Ok(Control::Statement(body, env));
+                }
+            }
+            _ => {}
+        }
+        Err(Error::Stuck {
+            producer: value.to_string(),
+            consumer: covalue.to_string(),
+            location: self.location.clone(),
+        })
+    }
+
+    /// Runs a `prim` or `switch` once the values of its producers are known,
+    /// given the values of the first few of them.
+    /// Forces the next thunk among the others first, if any.
+    fn strict(
+        &self,
+        statement: &'a Statement<Name>,
+        env: &Env<'a>,
+        mut values: Vec<Value<'a>>,
+    ) -> Result<Control<'a>, Error> {
+        let producers = match &statement.kind {
+            StatementKind::Prim(Prim { producers, .. }) => producers.as_slice(),
+            StatementKind::Switch(Switch { scrutinee, .. }) => std::slice::from_ref(scrutinee),
+            _ => unreachable!("only `prim` and `switch` need the values of their producers"),
+        };
+        for producer in &producers[values.len()..] {
+            match self.value(producer, env) {
+                Value::Thunk(thunk) => {
+                    let value = thunk.value.borrow().clone();
+                    match value {
+                        Some(value) => values.push(value),
+                        None => {
+                            return Ok(Control::Cut(
+                                Value::Thunk(thunk),
+                                Covalue::Resume(statement, env.clone(), values),
+                            ))
+                        }
+                    }
+                }
+                value => values.push(value),
+            }
+        }
+        match &statement.kind {
+            StatementKind::Prim(prim) => self.prim(prim, statement, env, values),
+            StatementKind::Switch(switch) => self.switch(switch, statement, env, values),
+            _ => unreachable!("only `prim` and `switch` need the values of their producers"),
+        }
+    }
+
+    fn prim(
+        &self,
+        Prim {
+            name, consumers, ..
+        }: &'a Prim<Name>,
+        statement: &'a Statement<Name>,
+        env: &Env<'a>,
+        values: Vec<Value<'a>>,
+    ) -> Result<Control<'a>, Error> {
+        let failure = |message: String| Error::Primitive {
+            name: name.clone(),
+            message,
+            location: statement.location.clone(),
+        };
+        let primitive = self
+            .primitives
+            .get(name)
+            .ok_or_else(|| failure("not registered".to_string()))?;
+        let arguments = values
+            .into_iter()
+            .map(|value| match value {
+                Value::Literal(literal) => Ok(literal),
+                value => Err(failure(format!("`{}` is not a literal", value))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !primitive.pure {
+            self.effect.set(Some(name));
+        }
+        let (index, result) = (primitive.run)(&arguments).map_err(failure)?;
+        let consumer = consumers.get(index).ok_or_else(|| {
+            failure(format!(
+                "passed its result to consumer {} of {}",
+                index,
+                consumers.len()
+            ))
+        })?;
+        Ok(Control::Cut(
+            Value::Literal(result),
+            self.covalue(consumer, env),
+        ))
+    }
+
+    fn switch(
+        &self,
+        Switch { branches, .. }: &'a Switch<Name>,
+        statement: &'a Statement<Name>,
+        env: &Env<'a>,
+        mut values: Vec<Value<'a>>,
+    ) -> Result<Control<'a>, Error> {
+        let value = values.remove(0);
+        let branch = branches.iter().find_map(|branch| match &branch.kind {
+            BranchKind::LiteralBranch(LiteralBranch { literal, body }) => {
+                matches!(&value, Value::Literal(value) if exhaustive::same_literal(value, literal))
+                    .then_some(body)
+            }
+            BranchKind::DefaultBranch(body) => Some(body),
+        });
+        match branch {
+            Some(body) => Ok(Control::Statement(body, env.clone())),
+            None => Err(Error::NoBranch {
+                value: value.to_string(),
+                location: statement.location.clone(),
+            }),
+        }
+    }
+
+    fn value(&self, producer: &'a Producer<Name>, env: &Env<'a>) -> Value<'a> {
+        match &producer.kind {
+            ProducerKind::Variable(Variable { name }) => env.value(name),
+            ProducerKind::Literal(literal) => Value::Literal(literal.clone()),
+            ProducerKind::Construct(Construct {
+                tag,
+                producers,
+                consumers,
+            }) => Value::Construct(Rc::new(self.applied(tag, producers, consumers, env))),
+            ProducerKind::Comatch(comatch) => Value::Comatch(comatch, env.clone()),
+            ProducerKind::Do(_) => {
+                unreachable!("`do` is only cut directly, as programs are focused")
+            }
+            ProducerKind::Error => unreachable!("programs with errors are not run"),
+        }
+    }
+
+    fn covalue(&self, consumer: &'a Consumer<Name>, env: &Env<'a>) -> Covalue<'a> {
+        match &consumer.kind {
+            ConsumerKind::Finish => Covalue::Finish,
+            ConsumerKind::Variable(Variable { name }) => env.covalue(name),
+            ConsumerKind::Then(then) => Covalue::Then(then, env.clone()),
+            ConsumerKind::Destruct(Destruct {
+                tag,
+                producers,
+                consumers,
+            }) => Covalue::Destruct(Rc::new(self.applied(tag, producers, consumers, env))),
+            ConsumerKind::Match(r#match) => Covalue::Match(r#match, env.clone()),
+            ConsumerKind::Error => unreachable!("programs with errors are not run"),
+        }
+    }
+
+    fn applied(
+        &self,
+        tag: &'a str,
+        producers: &'a [Producer<Name>],
+        consumers: &'a [Consumer<Name>],
+        env: &Env<'a>,
+    ) -> Applied<'a> {
+        Applied {
+            tag,
+            values: producers
+                .iter()
+                .map(|producer| self.value(producer, env))
+                .collect(),
+            covalues: consumers
+                .iter()
+                .map(|consumer| self.covalue(consumer, env))
+                .collect(),
+            _allocation: self.heap.allocate(),
+        }
+    }
+}
+
+/// Binds the parameters and returns of a clause or coclause to the arguments of `applied`.
+fn bind<'a>(
+    heap: &Heap,
+    env: &Env<'a>,
+    parameters: &'a [Binder<Name>],
+    returns: &'a [Binder<Name>],
+    applied: &Applied<'a>,
+) -> Env<'a> {
+    let mut env = env.clone();
+    for (binder, value) in parameters.iter().zip(&applied.values) {
+        env = env.bind(heap, &binder.name, Binding::Value(value.clone()));
+    }
+    for (binder, covalue) in returns.iter().zip(&applied.covalues) {
+        env = env.bind(heap, &binder.name, Binding::Covalue(covalue.clone()));
+    }
+    env
+}
+
+/// Gives every `do` visited the strategy of the definition it is in.
+struct Strategies<'m> {
+    strategy: Strategy,
+    strategies: &'m mut HashMap<*const Do<Name>, Strategy>,
+}
+
+impl<'a> Visitor<'a, Name> for Strategies<'_> {
+    fn producer(&mut self, producer: &'a Producer<Name>) {
+        if let ProducerKind::Do(delayed) = &producer.kind {
+            self.strategies.insert(delayed as *const _, self.strategy);
+        }
+        visit::walk_producer(self, producer);
+    }
+}
+
+/// Reports a limit reached while running the statement at `location`.
+fn exhausted(resource: Resource, limit: usize, location: &location::Location) -> Error {
+    Error::Exhausted {
+        resource,
+        limit,
+        location: location.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::tests::program_with;
+    use crate::{focus, name};
+
+    const LIST: &str = "data List[a] { Nil(;), Cons(x: a, xs: List[a];) }\n";
+
+    /// Runs `entry` and returns its value printed, with the number of reductions it took.
+    fn run_with(
+        source: &str,
+        entry: &str,
+        strategy: Strategy,
+        limits: Limits,
+    ) -> Result<(String, usize), Error> {
+        let mut names = name::Supply::new();
+        let program = program_with(source, &mut names);
+        let program = focus::Focuser::new(&mut names).focus(program);
+        let primitives = prim::Registry::default();
+        let entry = program
+            .definitions
+            .iter()
+            .find(|definition| definition.name.text == entry)
+            .expect("the entry is defined");
+        let mut machine = Machine::new(&program, &primitives, entry, strategy);
+        machine.limit(limits);
+        let mut traced = Vec::new();
+        let value = machine.run_traced(|machine| traced.push(machine.steps()))?;
+        // Every reduction is traced, including those that force the final value.
+        traced.dedup();
+        assert!(traced.into_iter().eq(0..=machine.steps()));
+        Ok((value.to_string(), machine.steps()))
+    }
+
+    fn run(source: &str) -> Result<String, Error> {
+        run_with(source, "main", Strategy::Value, Limits::default()).map(|(value, _)| value)
+    }
+
+    #[test]
+    fn runs_matches_and_invocations() {
+        let source = format!(
+            "{}def len(xs; k) = xs | match {{\n\
+               Nil(;) -> 0 | k,\n\
+               Cons(x, ys;) -> invoke[len](ys; then n prim[add](n, 1; k))\n\
+             }}\n\
+             def main(; k) = invoke[len](Cons(1, Cons(2, Cons(3, Nil(;););););k)",
+            LIST
+        );
+        assert_eq!(run(&source).unwrap(), "3");
+    }
+
+    #[test]
+    fn runs_comatches() {
+        let source = "codata Pair { Fst(; k: Int), Snd(; k: Int) }\n\
+                      def main(; k) = comatch { Fst(; a) -> 1 | a, Snd(; b) -> 2 | b } | Snd(; k)";
+        assert_eq!(run(source).unwrap(), "2");
+    }
+
+    #[test]
+    fn prints_values_as_the_printer_does() {
+        let source = format!("{}def main(; k) = Cons(\"a\\n\", Nil(;);) | k", LIST);
+        assert_eq!(run(&source).unwrap(), "Cons(\"a\\n\", Nil(;);)");
+    }
+
+    #[test]
+    fn switches_compare_floats_by_value() {
+        let source = "def main(; k) = switch -0.0 { 0.0 -> 1 | k, _ -> 2 | k }";
+        assert_eq!(run(source).unwrap(), "1");
+        let source = "def main(; k) = switch nan { nan -> 1 | k, _ -> 2 | k }";
+        assert_eq!(run(source).unwrap(), "1");
+    }
+
+    #[test]
+    fn forces_the_final_value() {
+        let source = format!(
+            "{}def main(; k) = Cons(do a prim[add](1, 2; a), Nil(;);) | k",
+            LIST
+        );
+        let (value, steps) = run_with(&source, "main", Strategy::Name, Limits::default()).unwrap();
+        assert_eq!(value, "Cons(3, Nil(;);)");
+        assert!(steps > 1, "{}", steps);
+    }
+
+    /// The `do` fails if it runs, and its value is used twice if it is needed at all.
+    const DELAYED: &str = "def main(; k) = do a prim[add](1, 2; a) | then x prim[add](x, x; k)\n\
+                           def unused(; k) = do a prim[div](1, 0; a) | then x 1 | k";
+
+    #[test]
+    fn call_by_value() {
+        let (value, _) = run_with(DELAYED, "main", Strategy::Value, Limits::default()).unwrap();
+        assert_eq!(value, "6");
+        let error = run_with(DELAYED, "unused", Strategy::Value, Limits::default());
+        assert!(matches!(error, Err(Error::Primitive { .. })));
+    }
+
+    #[test]
+    fn call_by_name() {
+        let (value, by_name) =
+            run_with(DELAYED, "main", Strategy::Name, Limits::default()).unwrap();
+        assert_eq!(value, "6");
+        let (_, by_need) = run_with(DELAYED, "main", Strategy::Need, Limits::default()).unwrap();
+        assert!(by_name > by_need, "the `do` runs once for each use");
+        let (value, _) = run_with(DELAYED, "unused", Strategy::Name, Limits::default()).unwrap();
+        assert_eq!(value, "1");
+    }
+
+    #[test]
+    fn call_by_need() {
+        let (value, _) = run_with(DELAYED, "main", Strategy::Need, Limits::default()).unwrap();
+        assert_eq!(value, "6");
+        let (value, _) = run_with(DELAYED, "unused", Strategy::Need, Limits::default()).unwrap();
+        assert_eq!(value, "1");
+        // Thunks left in the final value are run before it is printed.
+        let source = format!(
+            "{}def main(; k) = do a prim[add](1, 2; a) | then x Cons(x, Nil(;);) | k",
+            LIST
+        );
+        let (value, _) = run_with(&source, "main", Strategy::Need, Limits::default()).unwrap();
+        assert_eq!(value, "Cons(3, Nil(;);)");
+    }
+
+    #[test]
+    fn strategy_attributes_override_the_default() {
+        let source = "@strategy(cbn)\n\
+                      def main(; k) = do a prim[div](1, 0; a) | then x 1 | k";
+        assert_eq!(run(source).unwrap(), "1");
+        let source = "@strategy(cbv)\n\
+                      def main(; k) = do a prim[div](1, 0; a) | then x 1 | k";
+        let error = run_with(source, "main", Strategy::Need, Limits::default());
+        assert!(matches!(error, Err(Error::Primitive { .. })));
+    }
+
+    /// Builds the list `Cons(1, ... Cons(n, Nil(;);) ...;)` in about 7n reductions.
+    fn build(n: usize) -> String {
+        format!(
+            "{}def build(n, acc; k) = switch n {{\n\
+               0 -> acc | k,\n\
+               _ -> prim[sub](n, 1; then m invoke[build](m, Cons(n, acc;); k))\n\
+             }}\n\
+             def main(; k) = invoke[build]({}, Nil(;); k)",
+            LIST, n
+        )
+    }
+
+    #[test]
+    fn limits() {
+        let exhausted = |limits, resource| {
+            let result = run_with(&build(100), "main", Strategy::Value, limits);
+            assert!(
+                matches!(result, Err(Error::Exhausted { resource: r, .. }) if r == resource),
+                "{:?}",
+                result.map(|(_, steps)| steps)
+            );
+        };
+        let limits = |steps, heap, depth| Limits { steps, heap, depth };
+        exhausted(limits(Some(100), None, None), Resource::Steps);
+        exhausted(limits(None, Some(100), None), Resource::Heap);
+        exhausted(limits(None, None, Some(3)), Resource::Depth);
+        let (value, steps) = run_with(
+            &build(100),
+            "main",
+            Strategy::Value,
+            limits(Some(1000), Some(1000), Some(4)),
+        )
+        .unwrap();
+        assert!(value.starts_with("Cons(1, Cons(2, "), "{}", value);
+        assert!(steps <= 1000);
+    }
+
+    #[test]
+    fn limits_are_reported_where_they_are_reached() {
+        let source = "def main(; k) = 1 | then x 2 | then y prim[add](x, y; k)";
+        let location = |limits| match run_with(source, "main", Strategy::Value, limits) {
+            Err(Error::Exhausted { location, .. }) => &source[location.start..location.end],
+            other => panic!("expected a limit to be reached, got {:?}", other),
+        };
+        let limits = |heap, depth| Limits {
+            steps: None,
+            heap,
+            depth,
+        };
+        assert_eq!(
+            location(limits(Some(2), None)),
+            "2 | then y prim[add](x, y; k)"
+        );
+        assert_eq!(
+            location(limits(None, Some(2))),
+            "2 | then y prim[add](x, y; k)"
+        );
+    }
+
+    #[test]
+    fn deep_values() {
+        let source = build(100_000);
+        for strategy in [Strategy::Value, Strategy::Name, Strategy::Need] {
+            // Dropping the state after running out must not overflow the stack either.
+            let limits = Limits {
+                steps: Some(350_000),
+                ..Limits::default()
+            };
+            let result = run_with(&source, "main", strategy, limits);
+            assert!(
+                matches!(
+                    result,
+                    Err(Error::Exhausted {
+                        resource: Resource::Steps,
+                        ..
+                    })
+                ),
+                "{}",
+                strategy
+            );
+            let (value, _) = run_with(&source, "main", strategy, Limits::default()).unwrap();
+            assert!(value.ends_with(&format!("Cons(100000, Nil(;);){}", ";)".repeat(99_999))));
+        }
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let error = run("def main(; k) = prim[div](1, 0; k)").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "primitive `div` failed: division by zero"
+        );
+        let error = run("def main(; k) = switch 2 { 1 -> 1 | k }").unwrap_err();
+        assert_eq!(error.to_string(), "no branch matches `2`");
+    }
+}
@@ -1,54 +1,544 @@
-mod core_ir;
-mod lexer;
-mod location;
-mod name;
-mod parser;
-mod token;
-
-use std::io;
-
-use ariadne::{ColorGenerator, Label, Report, ReportKind, Source};
-
-fn main() -> io::Result<()> {
-    let source = r#"
-        def mult(l; α) = invoke[multAux](l; α, α)
-        def multAux(l; α, β) =
-            l | match {
-                Nil(;) -> 1 | β,
-                Cons(x, xs;) -> switch x {
-                    0 -> 0 | α,
-                    _ -> invoke[multAux](xs; α, then z prim[mul](x, z; β)),
-                },
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::ExitCode;
+
+use ariadne::{Report, Source};
+use coco::{
+    arity, attribute, core_ir, debugger, exhaustive, focus, lexer, location, machine, name, parser,
+    prim, printer, resolve, session, suggest, typecheck,
+};
+use color_eyre::eyre::{Result, WrapErr};
+
+const USAGE: &str = "\
+usage: coco <command> [options] <file>...
+
+Commands:
+    lex FILE                        print the tokens of a program
+    parse [--from FORMAT] FILE      parse a program and print it back
+    check [--show-types] [--from FORMAT] FILE
+                                    report errors in a program
+    run [--entry NAME] [--strategy STRATEGY] [--trace] [--from FORMAT]
+        [--max-steps N] [--max-heap N] [--max-depth N] FILE
+                                    run a program from NAME (default `main`)
+    debug [--entry NAME] [--strategy STRATEGY] [--from FORMAT] FILE
+                                    run a program step by step
+    fmt [--check] FILE...           format programs in place
+    emit [--from FORMAT] [--to FORMAT] FILE
+                                    convert a program between formats
+
+FORMAT is `coco` (surface syntax, the default input) or `sexp` (S-expressions).
+STRATEGY is `cbv` (call-by-value, the default), `cbn` (call-by-name) or `need`
+(call-by-need); definitions may override it with `@strategy(...)`.
+FILE may be `-` to read from standard input, except with `debug`.
+--max-steps bounds the number of reductions, --max-heap the number of bindings,
+applications and delayed `do`s alive at once, and --max-depth the number of
+bindings in scope.
+
+Exit status is 0 on success, 1 if the program has errors or cannot be read,
+2 on invalid usage, and 3 if `run` reaches a --max-* limit.";
+
+/// The program has errors.
+const EXIT_ERRORS: u8 = 1;
+/// The command line is invalid.
+const EXIT_USAGE: u8 = 2;
+/// The program reached a limit given to `run`.
+const EXIT_EXHAUSTED: u8 = 3;
+
+fn main() -> Result<ExitCode> {
+    color_eyre::install()?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        return Ok(usage_error("missing command"));
+    };
+    match command.as_str() {
+        "lex" => lex(args),
+        "parse" => parse(args),
+        "check" => check(args),
+        "run" => run(args, false),
+        "debug" => run(args, true),
+        "fmt" => fmt(args),
+        "emit" => emit(args),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        command => Ok(usage_error(&format!("unknown command {:?}", command))),
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("coco: {}\n\n{}", message, USAGE);
+    ExitCode::from(EXIT_USAGE)
+}
+
+/// Arguments of a subcommand.
+struct Args {
+    flags: Vec<String>,
+    options: HashMap<String, String>,
+    files: Vec<String>,
+}
+
+impl Args {
+    /// Splits `args` into the given boolean `flags`, `options` taking a value, and files.
+    /// Returns an error message for anything else starting with `--`.
+    fn parse(
+        args: &[String],
+        flags: &[&str],
+        options: &[&str],
+    ) -> std::result::Result<Self, String> {
+        let mut result = Args {
+            flags: Vec::new(),
+            options: HashMap::new(),
+            files: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if flags.contains(&arg.as_str()) {
+                result.flags.push(arg.clone());
+            } else if options.contains(&arg.as_str()) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value", arg))?;
+                result.options.insert(arg.clone(), value.clone());
+            } else if arg.starts_with("--") {
+                return Err(format!("unknown option {:?}", arg));
+            } else {
+                result.files.push(arg.clone());
             }
-        def hello(;α) = prim[print]("こんにちは"; α) 
-    "#;
+        }
+        Ok(result)
+    }
+
+    fn flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
 
-    let mut lexer = lexer::Lexer::new("source".to_string(), source);
-    let mut parser = parser::Parser::new(lexer.clone());
-    while let Some(token) = lexer.next_token() {
-        let mut colors = ColorGenerator::new();
-        let a = colors.next();
-        let report = Report::build(ReportKind::Advice, token.location.clone())
-            .with_message("found token")
-            .with_label(
-                Label::new(token.location.clone())
-                    .with_message(format!("{:?}", token.kind))
-                    .with_color(a),
-            )
-            .finish();
-        report.print(("source".to_string(), Source::from(source)))?;
+    fn option(&self, option: &str) -> Option<&str> {
+        self.options.get(option).map(String::as_str)
     }
 
-    let program = parser.parse();
-    match program {
-        Ok(program) => {
-            println!("{:#?}", program);
+    /// Returns the only file argument.
+    fn file(&self) -> std::result::Result<&str, String> {
+        match self.files.as_slice() {
+            [file] => Ok(file),
+            [] => Err("missing file".to_string()),
+            _ => Err("expected a single file".to_string()),
         }
-        Err(e) => {
-            let report: Report<location::Location> = e.into();
-            report.print(("source".to_string(), Source::from(source)))?;
+    }
+
+    /// Returns the value of an option taking a number.
+    fn count(&self, option: &str) -> std::result::Result<Option<usize>, String> {
+        self.option(option)
+            .map(|count| {
+                count
+                    .parse()
+                    .map_err(|_| format!("expected a number for {}, not {:?}", option, count))
+            })
+            .transpose()
+    }
+
+    /// Returns the value of a `--from` or `--to` option.
+    fn format(&self, option: &str) -> std::result::Result<Option<&'static str>, String> {
+        match self.option(option) {
+            None => Ok(None),
+            Some("coco") => Ok(Some("coco")),
+            Some("sexp") => Ok(Some("sexp")),
+            Some(format) => Err(format!(
+                "unknown format {:?} for {}, expected coco or sexp",
+                format, option
+            )),
         }
     }
+}
+
+/// Reads a file, or standard input if `path` is `-`.
+/// Returns the name to use in diagnostics together with the contents.
+fn read_source(path: &str) -> Result<(String, String)> {
+    if path == "-" {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .wrap_err("cannot read standard input")?;
+        Ok(("<stdin>".to_string(), source))
+    } else {
+        let source =
+            std::fs::read_to_string(path).wrap_err_with(|| format!("cannot read {}", path))?;
+        Ok((path.to_string(), source))
+    }
+}
 
+/// Reports `errors` at their locations in `source`.
+/// Without a source, as for a program loaded from S-expressions, only their messages are shown.
+fn report_all<E>(
+    file: &str,
+    source: Option<&str>,
+    errors: impl IntoIterator<Item = E>,
+) -> Result<()>
+where
+    E: Into<Report<'static, location::Location>> + std::fmt::Display,
+{
+    for error in errors {
+        match source {
+            Some(source) => {
+                let report: Report<location::Location> = error.into();
+                report.eprint((file.to_string(), Source::from(source)))?;
+            }
+            None => eprintln!("{}: {}", file, error),
+        }
+    }
     Ok(())
 }
+
+/// Returns the source that locations in a program loaded from `format` point into, if any.
+fn located<'a>(source: &'a str, format: &str) -> Option<&'a str> {
+    (format == "coco").then_some(source)
+}
+
+/// Loads a program written in `format`, reporting any errors.
+/// Returns `None` if the program has errors.
+fn load(file: &str, source: &str, format: &str) -> Result<Option<core_ir::Program<String>>> {
+    match format {
+        "coco" => {
+            let lexer = lexer::Lexer::new(file.to_string(), source);
+            let (program, errors) = parser::Parser::new(lexer).parse();
+            if errors.is_empty() {
+                Ok(Some(program))
+            } else {
+                report_all(file, Some(source), errors)?;
+                Ok(None)
+            }
+        }
+        "sexp" => match serde_lexpr::from_str(source) {
+            Ok(program) => Ok(Some(program)),
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                Ok(None)
+            }
+        },
+        _ => unreachable!("formats are validated by `Args::format`"),
+    }
+}
+
+/// `coco lex FILE`
+fn lex(args: &[String]) -> Result<ExitCode> {
+    let path = match Args::parse(args, &[], &[]).and_then(|args| Ok(args.file()?.to_string())) {
+        Ok(path) => path,
+        Err(message) => return Ok(usage_error(&message)),
+    };
+    let (file, source) = read_source(&path)?;
+
+    let mut status = ExitCode::SUCCESS;
+    for token in lexer::Lexer::new(file.clone(), &source) {
+        match token {
+            Ok(token) => println!(
+                "{}..{}\t{:?}",
+                token.location.start, token.location.end, token.kind
+            ),
+            Err(e) => {
+                report_all(&file, Some(&source), [e])?;
+                status = ExitCode::from(EXIT_ERRORS);
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// `coco parse [--from FORMAT] FILE`
+fn parse(args: &[String]) -> Result<ExitCode> {
+    let (path, from) = match Args::parse(args, &[], &["--from"])
+        .and_then(|args| Ok((args.file()?.to_string(), args.format("--from")?)))
+    {
+        Ok((path, from)) => (path, from.unwrap_or("coco")),
+        Err(message) => return Ok(usage_error(&message)),
+    };
+    let (file, source) = read_source(&path)?;
+
+    let Some(program) = load(&file, &source, from)? else {
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    };
+    print!("{}", printer(from, &source).print(&program));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns a printer that keeps the spelling of literals in a `coco` source.
+fn printer(format: &str, source: &str) -> printer::Printer {
+    match format {
+        "coco" => printer::Printer::with_source(printer::DEFAULT_WIDTH, source, Vec::new()),
+        _ => printer::Printer::default(),
+    }
+}
+
+/// `coco check [--show-types] [--from FORMAT] FILE`
+///
+/// With `--show-types`, prints the inferred type of every definition.
+fn check(args: &[String]) -> Result<ExitCode> {
+    let (path, from, show_types) =
+        match Args::parse(args, &["--show-types"], &["--from"]).and_then(|args| {
+            Ok((
+                args.file()?.to_string(),
+                args.format("--from")?,
+                args.flag("--show-types"),
+            ))
+        }) {
+            Ok((path, from, show_types)) => (path, from.unwrap_or("coco"), show_types),
+            Err(message) => return Ok(usage_error(&message)),
+        };
+    let (file, source) = read_source(&path)?;
+
+    let Some(program) = load(&file, &source, from)? else {
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    };
+    let mut session = session::Session::new();
+    let Some((_, schemes)) = analyse(&file, located(&source, from), program, &mut session)? else {
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    };
+    if show_types {
+        for (name, scheme) in schemes {
+            println!("{} : {}", name.text, scheme);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// A resolved program with the inferred type of every definition.
+type Analysed = (
+    core_ir::Program<name::Name>,
+    Vec<(name::Name, typecheck::Scheme)>,
+);
+
+/// Resolves and checks a loaded program as `coco check` does,
+/// reporting any errors and warnings. Returns `None` if the program has errors.
+fn analyse(
+    file: &str,
+    source: Option<&str>,
+    program: core_ir::Program<String>,
+    session: &mut session::Session,
+) -> Result<Option<Analysed>> {
+    let (program, errors) = resolve::Resolver::new(&mut session.names).resolve(program);
+    if !errors.is_empty() {
+        report_all(file, source, errors)?;
+        return Ok(None);
+    }
+    let errors = arity::Checker::new(&program).check();
+    if !errors.is_empty() {
+        report_all(file, source, errors)?;
+        return Ok(None);
+    }
+    let errors = attribute::Checker::new(&program).check();
+    if !errors.is_empty() {
+        report_all(file, source, errors)?;
+        return Ok(None);
+    }
+    let errors = prim::Checker::new(&session.primitives).check(&program);
+    if !errors.is_empty() {
+        report_all(file, source, errors)?;
+        return Ok(None);
+    }
+    let (typing, errors) = typecheck::Checker::new(&program, &session.primitives).check();
+    if !errors.is_empty() {
+        report_all(file, source, errors)?;
+        return Ok(None);
+    }
+    let (errors, warnings): (Vec<_>, Vec<_>) = exhaustive::Checker::new(&program, &typing.clauses)
+        .check()
+        .into_iter()
+        .partition(exhaustive::Error::is_error);
+    report_all(file, source, warnings)?;
+    if !errors.is_empty() {
+        report_all(file, source, errors)?;
+        return Ok(None);
+    }
+    Ok(Some((program, typing.schemes)))
+}
+
+/// `coco run [--entry NAME] [--strategy STRATEGY] [--trace] [--from FORMAT]
+/// [--max-steps N] [--max-heap N] [--max-depth N] FILE`
+///
+/// Checks the program, then runs the definition NAME (`main` by default)
+/// with `finish` for each of its consumers and prints the value it finishes with.
+/// With `--trace`, every reduction is shown on standard error.
+/// The `--max-*` options stop the program once it uses too much.
+///
+/// `coco debug [--entry NAME] [--strategy STRATEGY] [--from FORMAT] FILE`
+///
+/// Runs the program under the debugger, which reads commands from standard input.
+fn run(args: &[String], debug: bool) -> Result<ExitCode> {
+    let flags: &[&str] = if debug { &[] } else { &["--trace"] };
+    let options: &[&str] = if debug {
+        &["--entry", "--strategy", "--from"]
+    } else {
+        &[
+            "--entry",
+            "--strategy",
+            "--from",
+            "--max-steps",
+            "--max-heap",
+            "--max-depth",
+        ]
+    };
+    let (path, from, entry, strategy, trace, limits) = match Args::parse(args, flags, options)
+        .and_then(|args| {
+            let strategy = match args.option("--strategy") {
+                None => machine::Strategy::default(),
+                Some(strategy) => strategy.parse().map_err(|()| {
+                    format!(
+                        "unknown strategy {:?} for --strategy, expected cbv, cbn or need",
+                        strategy
+                    )
+                })?,
+            };
+            Ok((
+                args.file()?.to_string(),
+                args.format("--from")?,
+                args.option("--entry").unwrap_or("main").to_string(),
+                strategy,
+                args.flag("--trace"),
+                machine::Limits {
+                    steps: args.count("--max-steps")?,
+                    heap: args.count("--max-heap")?,
+                    depth: args.count("--max-depth")?,
+                },
+            ))
+        }) {
+        Ok((path, ..)) if debug && path == "-" => {
+            return Ok(usage_error(
+                "debug reads commands from standard input, so FILE cannot be `-`",
+            ))
+        }
+        Ok((path, from, entry, strategy, trace, limits)) => {
+            (path, from.unwrap_or("coco"), entry, strategy, trace, limits)
+        }
+        Err(message) => return Ok(usage_error(&message)),
+    };
+    let (file, source) = read_source(&path)?;
+
+    let Some(program) = load(&file, &source, from)? else {
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    };
+    let mut session = session::Session::new();
+    let Some((program, _)) = analyse(&file, located(&source, from), program, &mut session)? else {
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    };
+    let program = focus::Focuser::new(&mut session.names).focus(program);
+
+    let Some(definition) = program
+        .definitions
+        .iter()
+        .find(|definition| definition.name.text == entry)
+    else {
+        let similar = suggest::closest(
+            &entry,
+            program
+                .definitions
+                .iter()
+                .map(|definition| (definition.name.text.as_str(), &definition.name.text)),
+        );
+        match similar {
+            Some(similar) => eprintln!(
+                "coco: no definition named `{}`, did you mean `{}`?",
+                entry, similar
+            ),
+            None => eprintln!("coco: no definition named `{}`", entry),
+        }
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    };
+    if !definition.parameters.is_empty() {
+        eprintln!("coco: `{}` takes producers, so it cannot be run", entry);
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    }
+
+    let mut machine = machine::Machine::new(&program, &session.primitives, definition, strategy);
+    if debug {
+        debugger::Debugger::new(&program, machine, &file, located(&source, from))
+            .run(std::io::stdin().lock())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    machine.limit(limits);
+    let mut reported = Ok(());
+    let result = machine.run_traced(|machine| {
+        if trace && reported.is_ok() {
+            reported = report_all(&file, located(&source, from), [debugger::redex(machine)]);
+        }
+    });
+    reported?;
+    match result {
+        Ok(value) => {
+            println!("{}", value);
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(error) => {
+            let status = match error {
+                machine::Error::Exhausted { .. } => EXIT_EXHAUSTED,
+                _ => EXIT_ERRORS,
+            };
+            report_all(&file, located(&source, from), [error])?;
+            Ok(ExitCode::from(status))
+        }
+    }
+}
+
+/// `coco fmt [--check] FILE...`
+///
+/// Rewrites each file in the canonical layout.
+/// With `--check`, files are left untouched and the exit code is non-zero
+/// if any of them is not formatted.
+/// Standard input is formatted to standard output.
+fn fmt(args: &[String]) -> Result<ExitCode> {
+    let args = match Args::parse(args, &["--check"], &[]) {
+        Ok(args) if args.files.is_empty() => return Ok(usage_error("missing file")),
+        Ok(args) => args,
+        Err(message) => return Ok(usage_error(&message)),
+    };
+    let check = args.flag("--check");
+    let mut status = ExitCode::SUCCESS;
+
+    for path in &args.files {
+        let (file, source) = read_source(path)?;
+        match printer::format(file.clone(), &source, printer::DEFAULT_WIDTH) {
+            Ok(formatted) if check => {
+                if formatted != source {
+                    eprintln!("{} is not formatted", file);
+                    status = ExitCode::from(EXIT_ERRORS);
+                }
+            }
+            Ok(formatted) if path == "-" => print!("{}", formatted),
+            Ok(formatted) if formatted != source => std::fs::write(path, formatted)
+                .wrap_err_with(|| format!("cannot write {}", path))?,
+            Ok(_) => {}
+            Err(errors) => {
+                report_all(&file, Some(&source), errors)?;
+                status = ExitCode::from(EXIT_ERRORS);
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+/// `coco emit [--from FORMAT] [--to FORMAT] FILE`
+///
+/// Converts a program between the surface syntax (`coco`) and
+/// S-expressions of the core IR (`sexp`). Defaults to `--from coco --to sexp`.
+fn emit(args: &[String]) -> Result<ExitCode> {
+    let (path, from, to) = match Args::parse(args, &[], &["--from", "--to"]).and_then(|args| {
+        Ok((
+            args.file()?.to_string(),
+            args.format("--from")?,
+            args.format("--to")?,
+        ))
+    }) {
+        Ok((path, from, to)) => (path, from.unwrap_or("coco"), to.unwrap_or("sexp")),
+        Err(message) => return Ok(usage_error(&message)),
+    };
+    let (file, source) = read_source(&path)?;
+
+    let Some(program) = load(&file, &source, from)? else {
+        return Ok(ExitCode::from(EXIT_ERRORS));
+    };
+    match to {
+        "coco" => print!("{}", printer(from, &source).print(&program)),
+        _ => println!("{}", serde_lexpr::to_string(&program)?),
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -1,15 +1,67 @@
+use std::fmt;
+
+/// A resolved name.
+/// Names with the same `text` are distinguished by `unique`,
+/// which is drawn from the `Supply` of the current session.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Name {
     pub text: String,
     pub unique: usize,
 }
 
-static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}#{}", self.text, self.unique)
+    }
+}
+
+/// Hands out fresh `Name`s.
+/// Each compilation session owns one, so unique ids are deterministic
+/// regardless of what else runs in the same process.
+#[derive(Debug, Default)]
+pub struct Supply {
+    next: usize,
+}
+
+impl Supply {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fresh(&mut self, text: String) -> Name {
+        let unique = self.next;
+        self.next += 1;
+        Name { text, unique }
+    }
+
+    /// Returns a fresh name with the same text as `name`, for hygienic renaming.
+    pub fn fresh_like(&mut self, name: &Name) -> Name {
+        self.fresh(name.text.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::tests::program;
+
+    #[test]
+    fn supplies_are_independent() {
+        let mut first = Supply::new();
+        let x = first.fresh("x".to_string());
+        let y = first.fresh_like(&x);
+        assert_eq!(x.to_string(), "x#0");
+        assert_eq!(y.to_string(), "x#1");
+        assert_ne!(x, y);
+        assert_eq!(Supply::new().fresh("x".to_string()), x);
+    }
 
-impl Name {
-    pub fn new(text: String) -> Self {
-        Self {
-            text,
-            unique: COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst),
-        }
+    #[test]
+    fn resolution_is_deterministic() {
+        let source = "def f(x; k) = do a x | a | then y invoke[g](; k)\ndef g(; k) = 1 | k";
+        let resolve = || format!("{:?}", program(source));
+        let first = resolve();
+        assert!(first.contains("unique: 0"));
+        assert_eq!(resolve(), first);
     }
 }
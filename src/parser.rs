@@ -16,6 +16,13 @@ pub enum Error {
     },
     #[error("unexpected end of file")]
     UnexpectedEOF { last: location::Location },
+    #[error("`{word}` is a reserved word")]
+    ReservedWord {
+        word: String,
+        location: location::Location,
+    },
+    #[error(transparent)]
+    Lexical(#[from] lexer::Error),
 }
 
 /// Convert Error to a ariadne::Report.
@@ -32,40 +39,179 @@ impl From<Error> for ariadne::Report<'_, location::Location> {
                     .finish()
             }
             Error::UnexpectedEOF { last } => Report::build(ReportKind::Error, last.clone())
+                .with_message("unexpected end of file")
                 .with_label(Label::new(last.clone()).with_message("unexpected end of file"))
                 .finish(),
+            Error::ReservedWord { word, location } => {
+                Report::build(ReportKind::Error, location.clone())
+                    .with_message(format!("`{}` is a reserved word", word))
+                    .with_label(Label::new(location).with_message("cannot be used as a name"))
+                    .finish()
+            }
+            Error::Lexical(error) => error.into(),
         }
     }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Identifiers that cannot be used as names.
+const KEYWORDS: &[&str] = &[
+    "def", "data", "codata", "do", "then", "match", "comatch", "finish", "prim", "switch",
+    "invoke", "inf", "nan",
+];
+
+/// Syntax that can be replaced by an error node when it fails to parse.
+trait Recover: Sized {
+    fn recover(location: location::Location) -> Option<Self>;
+}
+
+impl Recover for Producer<String> {
+    fn recover(location: location::Location) -> Option<Self> {
+        Some(Producer {
+            location,
+            kind: ProducerKind::Error,
+        })
+    }
+}
+
+impl Recover for Consumer<String> {
+    fn recover(location: location::Location) -> Option<Self> {
+        Some(Consumer {
+            location,
+            kind: ConsumerKind::Error,
+        })
+    }
+}
+
+impl Recover for String {
+    fn recover(_: location::Location) -> Option<Self> {
+        None
+    }
+}
+
+impl Recover for Clause<String> {
+    fn recover(_: location::Location) -> Option<Self> {
+        None
+    }
+}
+
+impl Recover for Coclause<String> {
+    fn recover(_: location::Location) -> Option<Self> {
+        None
+    }
+}
+
+impl Recover for Binder<String> {
+    fn recover(_: location::Location) -> Option<Self> {
+        None
+    }
+}
+
+impl Recover for Type {
+    fn recover(_: location::Location) -> Option<Self> {
+        None
+    }
+}
+
+impl Recover for Signature {
+    fn recover(_: location::Location) -> Option<Self> {
+        None
+    }
+}
+
+impl Recover for Branch<String> {
+    fn recover(_: location::Location) -> Option<Self> {
+        None
+    }
+}
+
+/// Where `Parser::synchronize` stopped skipping tokens.
+enum Recovery {
+    /// At the separator of the enclosing list.
+    Separator,
+    /// At the end token of the enclosing list.
+    End,
+    /// At a token the enclosing list cannot recover from:
+    /// an unbalanced closing delimiter, the next item, or the end of file.
+    Abort,
+}
+
 pub struct Parser<'a> {
     lexer: lexer::Lexer<'a>,
+    /// The next token, once `peek` has lexed it.
+    lookahead: Option<Token>,
     last_location: location::Location,
+    errors: Vec<Error>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: lexer::Lexer<'a>) -> Self {
+        let last_location = location::Location {
+            file: lexer.file.clone(),
+            start: lexer.get_cursor(),
+            end: lexer.get_cursor(),
+        };
         Self {
-            lexer: lexer.clone(),
-            last_location: lexer.clone().next_token().unwrap().location.clone(),
+            lexer,
+            lookahead: None,
+            last_location,
+            errors: Vec::new(),
         }
     }
 
-    fn peek(&self) -> Result<Token> {
-        self.lexer.clone().next_token().ok_or(Error::UnexpectedEOF {
-            last: self.last_location.clone(),
-        })
+    /// Returns the next token without consuming it.
+    /// A lexical error is recorded and stands for an error token.
+    fn peek(&mut self) -> Result<Token> {
+        if self.lookahead.is_none() {
+            match self.lexer.next_token() {
+                Some(Ok(token)) => self.lookahead = Some(token),
+                Some(Err(error)) => {
+                    self.lookahead = Some(Token {
+                        location: error.location().clone(),
+                        kind: TokenKind::Error,
+                    });
+                    self.errors.push(error.into());
+                }
+                None => {
+                    return Err(Error::UnexpectedEOF {
+                        last: self.last_location.clone(),
+                    })
+                }
+            }
+        }
+        Ok(self.lookahead.clone().unwrap())
     }
 
     fn advance(&mut self) {
-        let token = self.lexer.next_token();
-        if let Some(token) = token {
-            self.last_location = token.location.clone();
+        if self.peek().is_ok() {
+            self.last_location = self.lookahead.take().unwrap().location;
         }
     }
 
+    /// Records an error, unless it is about a token that failed to lex:
+    /// that error has already been recorded by `peek`.
+    fn report(&mut self, error: Error) {
+        if !matches!(
+            &error,
+            Error::UnexpectedToken {
+                actual: Token {
+                    kind: TokenKind::Error,
+                    ..
+                },
+                ..
+            }
+        ) {
+            self.errors.push(error);
+        }
+    }
+
+    /// Returns the comments skipped so far, in source order.
+    /// Once `parse` has returned, these are all the comments of the input.
+    pub fn into_comments(self) -> Vec<Comment> {
+        self.lexer.trivia
+    }
+
     /// Expect the next token to be a keyword.
     /// If the next token is a expected keyword, consume it and return it.
     /// Otherwise, return an error.
@@ -111,33 +257,134 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn eof(&self) -> bool {
+    fn eof(&mut self) -> bool {
         self.peek().is_err()
     }
 
-    pub fn parse(&mut self) -> Result<Program<String>> {
-        let mut program = Vec::new();
+    fn is_keyword(&mut self, keyword: &str) -> bool {
+        matches!(self.peek(), Ok(Token { kind: TokenKind::Identifier(i), .. }) if i == keyword)
+    }
+
+    fn is_punctuation(&mut self, punctuation: &str) -> bool {
+        matches!(self.peek(), Ok(token) if token.is_punctuation(punctuation))
+    }
+
+    /// Returns the location of the next token, or of the last token at the end of file.
+    fn next_location(&mut self) -> location::Location {
+        self.peek()
+            .map(|token| token.location)
+            .unwrap_or_else(|_| self.last_location.clone())
+    }
+
+    /// Returns the location from `start` to the last consumed token.
+    fn location_since(&self, start: &location::Location) -> location::Location {
+        if self.last_location.end >= start.start {
+            start.to(&self.last_location)
+        } else {
+            start.clone()
+        }
+    }
+
+    /// Parses the whole input.
+    /// Every error is collected instead of stopping at the first one,
+    /// and the returned program contains error nodes where parsing failed.
+    pub fn parse(&mut self) -> (Program<String>, Vec<Error>) {
+        let mut program = Program {
+            declarations: Vec::new(),
+            definitions: Vec::new(),
+        };
 
         while !self.eof() {
-            program.push(self.definition()?);
+            let result = if self.is_keyword("data") || self.is_keyword("codata") {
+                self.declaration()
+                    .map(|declaration| program.declarations.push(declaration))
+            } else {
+                self.definition()
+                    .map(|definition| program.definitions.push(definition))
+            };
+            if let Err(error) = result {
+                self.report(error);
+                self.skip_to_item();
+            }
         }
 
-        Ok(program)
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    /// Returns true if the next token starts a top-level item.
+    fn is_item_start(&mut self) -> bool {
+        self.is_keyword("def")
+            || self.is_keyword("data")
+            || self.is_keyword("codata")
+            || self.is_punctuation("@")
+    }
+
+    /// Skips tokens until the next item or the end of file.
+    fn skip_to_item(&mut self) {
+        while !self.eof() && !self.is_item_start() {
+            self.advance();
+        }
+    }
+
+    /// Skips tokens until `sep` or `end` at the current nesting level,
+    /// or until a token that the current list cannot recover from.
+    /// The token it stops at is not consumed.
+    fn synchronize(&mut self, end: &str, sep: &str) -> Recovery {
+        let mut depth = 0;
+        loop {
+            let Ok(token) = self.peek() else {
+                return Recovery::Abort;
+            };
+            match &token.kind {
+                TokenKind::Punctuation(p) if depth == 0 && p == sep => return Recovery::Separator,
+                TokenKind::Punctuation(p) if depth == 0 && p == end => return Recovery::End,
+                TokenKind::Punctuation(p) if matches!(p.as_str(), "(" | "[" | "{") => depth += 1,
+                TokenKind::Punctuation(p) if matches!(p.as_str(), ")" | "]" | "}") => {
+                    if depth == 0 {
+                        return Recovery::Abort;
+                    }
+                    depth -= 1;
+                }
+                TokenKind::Identifier(i) if matches!(i.as_str(), "def" | "data" | "codata") => {
+                    return Recovery::Abort
+                }
+                TokenKind::Punctuation(p) if p == "@" => return Recovery::Abort,
+                _ => {}
+            }
+            self.advance();
+        }
     }
 
     fn definition(&mut self) -> Result<Definition<String>> {
+        let mut attributes = Vec::new();
+        while self.is_punctuation("@") {
+            attributes.push(self.attribute()?);
+        }
         let def = self.expect_keyword("def")?;
         let (name, _) = self.identifier()?;
         self.expect_punctuation("(")?;
-        let (parameters, _) =
-            self.sep_end(";", ",", |parser| parser.identifier().map(|(name, _)| name))?;
-        let (returns, _) =
-            self.sep_end(")", ",", |parser| parser.identifier().map(|(name, _)| name))?;
+        let (parameters, _) = self.sep_end(";", ",", |parser| parser.binder())?;
+        let (returns, _) = self.sep_end(")", ",", |parser| parser.binder())?;
         self.expect_punctuation("=")?;
-        let body = self.statement()?;
-        let location = def.location.to(&body.location);
+        let start = self.next_location();
+        let body = match self.statement() {
+            Ok(body) => body,
+            Err(error) => {
+                self.report(error);
+                self.skip_to_item();
+                Statement {
+                    location: self.location_since(&start),
+                    kind: StatementKind::Error,
+                }
+            }
+        };
+        let location = attributes
+            .first()
+            .map_or(&def.location, |attribute| &attribute.location)
+            .to(&body.location);
         Ok(Definition {
             location,
+            attributes,
             name,
             parameters,
             returns,
@@ -145,12 +392,140 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parses `@name` or `@name(argument, ...)`.
+    fn attribute(&mut self) -> Result<Attribute> {
+        let at = self.expect_punctuation("@")?;
+        let (name, location) = self.identifier()?;
+        let (arguments, location) = if self.is_punctuation("(") {
+            self.advance();
+            let (arguments, right_paren) =
+                self.sep_end(")", ",", |parser| parser.identifier().map(|(name, _)| name))?;
+            (arguments, right_paren.location)
+        } else {
+            (Vec::new(), location)
+        };
+        Ok(Attribute {
+            location: at.location.to(&location),
+            name,
+            arguments,
+        })
+    }
+
+    fn declaration(&mut self) -> Result<Declaration> {
+        let (kind, keyword) = if self.is_keyword("data") {
+            (DeclarationKind::Data, self.expect_keyword("data")?)
+        } else {
+            (DeclarationKind::Codata, self.expect_keyword("codata")?)
+        };
+        let (name, _) = self.identifier()?;
+        let parameters = self
+            .type_parameters(|parser| parser.identifier().map(|(name, _)| name))?
+            .unwrap_or_default();
+        self.expect_punctuation("{")?;
+        let (signatures, right_brace) = self.sep_end("}", ",", |parser| parser.signature())?;
+        let location = keyword.location.to(&right_brace.location);
+
+        Ok(Declaration {
+            location,
+            kind,
+            name,
+            parameters,
+            signatures,
+        })
+    }
+
+    fn signature(&mut self) -> Result<Signature> {
+        let (tag, location) = self.identifier()?;
+        self.expect_punctuation("(")?;
+        let (parameters, _) = self.sep_end(";", ",", |parser| parser.binder())?;
+        let (returns, right_paren) = self.sep_end(")", ",", |parser| parser.binder())?;
+        let location = location.to(&right_paren.location);
+
+        Ok(Signature {
+            location,
+            tag,
+            parameters,
+            returns,
+        })
+    }
+
+    /// Parses `x` or `x: T`.
+    fn binder(&mut self) -> Result<Binder<String>> {
+        let (name, location) = self.identifier()?;
+        let ty = match self.peek() {
+            Ok(Token {
+                kind: TokenKind::Punctuation(p),
+                ..
+            }) if p == ":" => {
+                self.advance();
+                Some(self.ty()?)
+            }
+            _ => None,
+        };
+        Ok(Binder {
+            location: self.location_since(&location),
+            name,
+            ty,
+        })
+    }
+
+    /// Parses `x`, a binder that cannot be annotated with a type.
+    fn unannotated_binder(&mut self) -> Result<Binder<String>> {
+        let (name, location) = self.identifier()?;
+        Ok(Binder {
+            location,
+            name,
+            ty: None,
+        })
+    }
+
+    /// Parses a type: `Int`, `List[a]`, or a type variable starting with a lowercase letter.
+    fn ty(&mut self) -> Result<Type> {
+        let (name, _) = self.identifier()?;
+        if name.starts_with(|c: char| c.is_lowercase()) {
+            return Ok(Type::Variable(name));
+        }
+        let arguments = self
+            .type_parameters(|parser| parser.ty())?
+            .unwrap_or_default();
+        Ok(Type::Named(name, arguments))
+    }
+
+    /// Parses `[x, y]` if the next token is `[`.
+    fn type_parameters<T: Recover>(
+        &mut self,
+        parser: impl Fn(&mut Self) -> Result<T>,
+    ) -> Result<Option<Vec<T>>> {
+        match self.peek() {
+            Ok(Token {
+                kind: TokenKind::Punctuation(p),
+                ..
+            }) if p == "[" => {
+                self.advance();
+                let (items, _) = self.sep_end("]", ",", parser)?;
+                Ok(Some(items))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn identifier(&mut self) -> Result<(String, location::Location)> {
         let token = self.peek()?;
 
         if let TokenKind::Identifier(ident) = &token.kind {
+            if KEYWORDS.contains(&ident.as_str()) {
+                return Err(Error::ReservedWord {
+                    word: ident.clone(),
+                    location: token.location,
+                });
+            }
             self.advance();
             Ok((ident.clone(), token.location))
+        } else if let TokenKind::Literal(Literal::Bool(value)) = &token.kind {
+            Err(Error::ReservedWord {
+                word: value.to_string(),
+                location: token.location,
+            })
         } else {
             Err(Error::UnexpectedToken {
                 expected: vec!["identifier".to_string()],
@@ -160,23 +535,43 @@ impl<'a> Parser<'a> {
     }
 
     fn statement(&mut self) -> Result<Statement<String>> {
-        match self.peek()? {
-            Token {
-                kind: TokenKind::Identifier(ident),
-                ..
-            } => match ident.as_str() {
+        if let Token {
+            kind: TokenKind::Identifier(ident),
+            ..
+        } = self.peek()?
+        {
+            match ident.as_str() {
                 "prim" => return self.prim(),
                 "switch" => return self.switch(),
                 "invoke" => return self.invoke(),
                 _ => {}
-            },
-            _ => {}
+            }
         }
         let producer = self.producer()?;
         self.postfix_statement(producer)
     }
 
+    /// Parses a literal token, or one of the words `inf` and `nan`.
+    fn literal(&mut self) -> Result<Option<(Literal, location::Location)>> {
+        let token = self.peek()?;
+        let literal = match token.kind {
+            TokenKind::Literal(literal) => literal,
+            TokenKind::Identifier(ident) if ident == "inf" => Literal::Float(f64::INFINITY),
+            TokenKind::Identifier(ident) if ident == "nan" => Literal::Float(f64::NAN),
+            _ => return Ok(None),
+        };
+        self.advance();
+        Ok(Some((literal, token.location)))
+    }
+
     fn producer(&mut self) -> Result<Producer<String>> {
+        if let Some((literal, location)) = self.literal()? {
+            return Ok(Producer {
+                location,
+                kind: ProducerKind::Literal(literal),
+            });
+        }
+
         if let Token {
             kind: TokenKind::Identifier(i),
             ..
@@ -185,46 +580,85 @@ impl<'a> Parser<'a> {
             if i == "do" {
                 return self.do_();
             }
+            if i == "comatch" {
+                return self.comatch();
+            }
             return self.variable();
         }
 
-        if let Token {
-            kind: TokenKind::Literal(literal),
-            location,
-        } = self.peek()?
-        {
-            self.advance();
-            return Ok(Producer {
-                location,
-                kind: ProducerKind::Literal(literal),
-            });
-        }
-
-        todo!()
+        Err(Error::UnexpectedToken {
+            expected: vec!["producer".to_string()],
+            actual: self.peek()?,
+        })
     }
 
     fn do_(&mut self) -> Result<Producer<String>> {
         let do_token = self.expect_keyword("do")?;
-        let (name, _) = self.identifier()?;
+        let binder = self.unannotated_binder()?;
         let statement = self.statement()?;
         let location = do_token.location.to(&statement.location);
 
         Ok(Producer {
             location,
             kind: ProducerKind::Do(Do {
-                name,
+                binder,
                 body: Box::new(statement),
             }),
         })
     }
 
+    fn comatch(&mut self) -> Result<Producer<String>> {
+        let comatch = self.expect_keyword("comatch")?;
+        self.expect_punctuation("{")?;
+        let (clauses, right_brace): (Vec<_>, _) =
+            self.sep_end("}", ",", |parser| parser.coclause())?;
+
+        let location = comatch.location.to(&right_brace.location);
+
+        Ok(Producer {
+            location,
+            kind: ProducerKind::Comatch(Comatch { clauses }),
+        })
+    }
+
+    fn coclause(&mut self) -> Result<Coclause<String>> {
+        let (copattern, location) = self.copattern()?;
+        self.expect_punctuation("->")?;
+        let body = self.statement()?;
+        let location = location.to(&body.location);
+
+        Ok(Coclause {
+            location,
+            copattern,
+            body,
+        })
+    }
+
+    fn copattern(&mut self) -> Result<(Copattern<String>, location::Location)> {
+        let (tag, location) = self.identifier()?;
+        self.expect_punctuation("(")?;
+        let (parameters, _) = self.sep_end(";", ",", |parser| parser.unannotated_binder())?;
+        let (returns, right_paren) =
+            self.sep_end(")", ",", |parser| parser.unannotated_binder())?;
+        let location = location.to(&right_paren.location);
+
+        Ok((
+            Copattern {
+                tag,
+                parameters,
+                returns,
+            },
+            location,
+        ))
+    }
+
     fn variable(&mut self) -> Result<Producer<String>> {
         let (name, location) = self.identifier()?;
         // if next token is '(', then it is a constructor application
-        if let Token {
+        if let Ok(Token {
             kind: TokenKind::Punctuation(p),
             ..
-        } = self.peek()?
+        }) = self.peek()
         {
             if p == "(" {
                 return self.construct(name, location);
@@ -272,22 +706,33 @@ impl<'a> Parser<'a> {
                 return self.match_();
             }
 
+            if i == "finish" {
+                let finish = self.expect_keyword("finish")?;
+                return Ok(Consumer {
+                    location: finish.location,
+                    kind: ConsumerKind::Finish,
+                });
+            }
+
             return self.covariable();
         }
 
-        todo!()
+        Err(Error::UnexpectedToken {
+            expected: vec!["consumer".to_string()],
+            actual: self.peek()?,
+        })
     }
 
     fn then(&mut self) -> Result<Consumer<String>> {
         let then = self.expect_keyword("then")?;
-        let (name, _) = self.identifier()?;
+        let binder = self.unannotated_binder()?;
         let body = self.statement()?;
         let location = then.location.to(&body.location);
 
         Ok(Consumer {
             location,
             kind: ConsumerKind::Then(Then {
-                name,
+                binder,
                 body: Box::new(body),
             }),
         })
@@ -323,10 +768,9 @@ impl<'a> Parser<'a> {
     fn pattern(&mut self) -> Result<(Pattern<String>, location::Location)> {
         let (tag, location) = self.identifier()?;
         self.expect_punctuation("(")?;
-        let (parameters, _) =
-            self.sep_end(";", ",", |parser| parser.identifier().map(|(name, _)| name))?;
+        let (parameters, _) = self.sep_end(";", ",", |parser| parser.unannotated_binder())?;
         let (returns, right_paren) =
-            self.sep_end(")", ",", |parser| parser.identifier().map(|(name, _)| name))?;
+            self.sep_end(")", ",", |parser| parser.unannotated_binder())?;
         let location = location.to(&right_paren.location);
 
         Ok((
@@ -341,12 +785,40 @@ impl<'a> Parser<'a> {
 
     fn covariable(&mut self) -> Result<Consumer<String>> {
         let (name, location) = self.identifier()?;
+        // if next token is '(', then it is a destructor application
+        if let Ok(Token {
+            kind: TokenKind::Punctuation(p),
+            ..
+        }) = self.peek()
+        {
+            if p == "(" {
+                return self.destruct(name, location);
+            }
+        }
+
         Ok(Consumer {
             location,
             kind: ConsumerKind::Variable(Variable { name }),
         })
     }
 
+    fn destruct(&mut self, name: String, location: location::Location) -> Result<Consumer<String>> {
+        self.expect_punctuation("(")?;
+        let (producers, _) = self.sep_end(";", ",", |parser| parser.producer())?;
+        let (consumers, right_paren) = self.sep_end(")", ",", |parser| parser.consumer())?;
+
+        let location = location.to(&right_paren.location);
+
+        Ok(Consumer {
+            location,
+            kind: ConsumerKind::Destruct(Destruct {
+                tag: name,
+                producers,
+                consumers,
+            }),
+        })
+    }
+
     fn postfix_statement(&mut self, producer: Producer<String>) -> Result<Statement<String>> {
         match self.peek()? {
             Token {
@@ -425,12 +897,7 @@ impl<'a> Parser<'a> {
         }
 
         // Literal branch
-        if let Token {
-            kind: TokenKind::Literal(literal),
-            location,
-        } = self.peek()?
-        {
-            self.advance();
+        if let Some((literal, location)) = self.literal()? {
             self.expect_punctuation("->")?;
             let body = self.statement()?;
             let location = location.to(&body.location);
@@ -447,7 +914,11 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn sep_end<T>(
+    /// Parses a list of items separated by `sep` and terminated by `end`.
+    /// If an item fails to parse, the error is recorded and parsing resumes
+    /// from the next `sep` or `end`, leaving an error node in place of the item.
+    /// Errors that cannot be recovered within the list are returned.
+    fn sep_end<T: Recover>(
         &mut self,
         end: &str,
         sep: &str,
@@ -467,9 +938,18 @@ impl<'a> Parser<'a> {
                 _ => {}
             }
 
-            // TODO: if the parser fails, probably we need the end token to recover
-            let item = parser(self)?;
-            items.push(item);
+            let start = self.next_location();
+            match parser(self) {
+                Ok(item) => items.push(item),
+                Err(error) => {
+                    let recovery = self.synchronize(end, sep);
+                    if let Recovery::Abort = recovery {
+                        return Err(error);
+                    }
+                    self.report(error);
+                    items.extend(T::recover(self.location_since(&start)));
+                }
+            }
 
             match self.peek()? {
                 Token {
@@ -487,10 +967,22 @@ impl<'a> Parser<'a> {
                     return Ok((items, token));
                 }
                 actual => {
-                    return Err(Error::UnexpectedToken {
+                    let error = Error::UnexpectedToken {
                         expected: vec![sep.to_string(), end.to_string()],
                         actual,
-                    });
+                    };
+                    match self.synchronize(end, sep) {
+                        Recovery::Separator => {
+                            self.report(error);
+                            self.advance();
+                        }
+                        Recovery::End => {
+                            self.report(error);
+                            let token = self.expect_punctuation(end)?;
+                            return Ok((items, token));
+                        }
+                        Recovery::Abort => return Err(error),
+                    }
                 }
             }
         }
@@ -520,3 +1012,271 @@ impl<'a> Parser<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> (Program<String>, Vec<Error>) {
+        Parser::new(lexer::Lexer::new("test.coco".to_string(), source)).parse()
+    }
+
+    /// Returns the text a location spans in an ASCII source.
+    fn text<'a>(source: &'a str, location: &location::Location) -> &'a str {
+        &source[location.start..location.end]
+    }
+
+    #[test]
+    fn nested_comatch() {
+        let source =
+            "def const(; a) = comatch { Ap(x; b) -> comatch { Ap(y; c) -> x | c } | b } | a";
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let StatementKind::Cut(Cut { producer, .. }) = &program.definitions[0].body.kind else {
+            panic!("expected a cut");
+        };
+        let ProducerKind::Comatch(Comatch { clauses }) = &producer.kind else {
+            panic!("expected a comatch");
+        };
+        assert_eq!(
+            text(source, &producer.location),
+            "comatch { Ap(x; b) -> comatch { Ap(y; c) -> x | c } | b }"
+        );
+        let [outer] = clauses.as_slice() else {
+            panic!("expected one clause");
+        };
+        assert_eq!(outer.copattern.tag, "Ap");
+        assert_eq!(
+            text(source, &outer.location),
+            "Ap(x; b) -> comatch { Ap(y; c) -> x | c } | b"
+        );
+        let StatementKind::Cut(Cut { producer, consumer }) = &outer.body.kind else {
+            panic!("expected a cut");
+        };
+        assert!(matches!(&consumer.kind, ConsumerKind::Variable(Variable { name }) if name == "b"));
+        let ProducerKind::Comatch(Comatch { clauses }) = &producer.kind else {
+            panic!("expected a nested comatch");
+        };
+        assert_eq!(
+            text(source, &producer.location),
+            "comatch { Ap(y; c) -> x | c }"
+        );
+        assert_eq!(text(source, &clauses[0].location), "Ap(y; c) -> x | c");
+    }
+
+    #[test]
+    fn comatch_with_several_clauses() {
+        let source =
+            "def s(; k) = comatch { Head(; h) -> 1 | h, Tail(; t) -> comatch {} | t, } | k";
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let StatementKind::Cut(Cut { producer, .. }) = &program.definitions[0].body.kind else {
+            panic!("expected a cut");
+        };
+        let ProducerKind::Comatch(Comatch { clauses }) = &producer.kind else {
+            panic!("expected a comatch");
+        };
+        let tags = clauses
+            .iter()
+            .map(|clause| clause.copattern.tag.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(tags, ["Head", "Tail"]);
+        assert!(clauses[0].copattern.parameters.is_empty());
+        let [h] = &clauses[0].copattern.returns[..] else {
+            panic!("expected one return");
+        };
+        assert_eq!(h.name, "h");
+        assert_eq!(text(source, &h.location), "h");
+    }
+
+    #[test]
+    fn destructor() {
+        let source = "def apply(f, x; k) = f | Ap(x, Cons(x, Nil(;);); then y y | k)";
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let StatementKind::Cut(Cut { consumer, .. }) = &program.definitions[0].body.kind else {
+            panic!("expected a cut");
+        };
+        let ConsumerKind::Destruct(Destruct {
+            tag,
+            producers,
+            consumers,
+        }) = &consumer.kind
+        else {
+            panic!("expected a destructor");
+        };
+        assert_eq!(tag, "Ap");
+        assert_eq!(producers.len(), 2);
+        assert!(matches!(
+            &consumers[..],
+            [Consumer {
+                kind: ConsumerKind::Then(_),
+                ..
+            }]
+        ));
+        assert_eq!(
+            text(source, &consumer.location),
+            "Ap(x, Cons(x, Nil(;);); then y y | k)"
+        );
+    }
+
+    #[test]
+    fn destructor_without_arguments() {
+        let (program, errors) = parse("def head(s; k) = s | Head(; k)");
+        assert!(errors.is_empty(), "{:?}", errors);
+        let StatementKind::Cut(Cut { consumer, .. }) = &program.definitions[0].body.kind else {
+            panic!("expected a cut");
+        };
+        assert!(matches!(
+            &consumer.kind,
+            ConsumerKind::Destruct(Destruct { tag, producers, consumers })
+                if tag == "Head" && producers.is_empty() && consumers.len() == 1
+        ));
+    }
+
+    #[test]
+    fn comments_survive_parsing() {
+        let source = "// leading\ndef f(x; k) = x | k // trailing\n";
+        let mut parser = Parser::new(lexer::Lexer::new("test.coco".to_string(), source));
+        let (_, errors) = parser.parse();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let comments: Vec<_> = parser
+            .into_comments()
+            .into_iter()
+            .map(|comment| comment.text)
+            .collect();
+        assert_eq!(comments, ["// leading", "// trailing"]);
+    }
+
+    #[test]
+    fn lexical_errors_do_not_cascade() {
+        let source = "def a(x; k) = $ | k\ndef b(x; k) = 1 | $\ndef c(x; k) = prim[add](x, $; k)";
+        let (program, errors) = parse(source);
+        assert_eq!(program.definitions.len(), 3);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors
+            .iter()
+            .all(|error| matches!(error, Error::Lexical(_))));
+    }
+
+    #[test]
+    fn recovery_reports_every_error() {
+        let source = "def a(x; k) = x |\ndef b(x 1; k) = x | k\ndef c(x; k) = x | k";
+        let (program, errors) = parse(source);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        let names: Vec<_> = program
+            .definitions
+            .iter()
+            .map(|definition| definition.name.as_str())
+            .collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert!(matches!(
+            program.definitions[0].body.kind,
+            StatementKind::Error
+        ));
+    }
+
+    #[test]
+    fn binder_locations() {
+        let source = "def f(x: Int; k) = do a x | then y y | match { Cons(z, zs;) -> z | a } | k";
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let definition = &program.definitions[0];
+        assert_eq!(text(source, &definition.parameters[0].location), "x: Int");
+        assert_eq!(text(source, &definition.returns[0].location), "k");
+        let StatementKind::Cut(Cut { producer, .. }) = &definition.body.kind else {
+            panic!("expected a cut");
+        };
+        let ProducerKind::Do(Do { binder, body }) = &producer.kind else {
+            panic!("expected a do");
+        };
+        assert_eq!(text(source, &binder.location), "a");
+        let StatementKind::Cut(Cut { consumer, .. }) = &body.kind else {
+            panic!("expected a cut");
+        };
+        let ConsumerKind::Then(Then { binder, body }) = &consumer.kind else {
+            panic!("expected a then");
+        };
+        assert_eq!((binder.name.as_str(), binder.location.start), ("y", 33));
+        let StatementKind::Cut(Cut { consumer, .. }) = &body.kind else {
+            panic!("expected a cut");
+        };
+        let ConsumerKind::Match(Match { clauses }) = &consumer.kind else {
+            panic!("expected a match");
+        };
+        let pattern = &clauses[0].pattern;
+        assert_eq!(text(source, &pattern.parameters[1].location), "zs");
+    }
+
+    #[test]
+    fn reserved_words() {
+        let (_, errors) = parse("def f(inf; k) = 1 | k\ndef g(true; k) = 1 | k");
+        let words: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                Error::ReservedWord { word, .. } => word.as_str(),
+                other => panic!("expected a reserved word error, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(words, ["inf", "true"]);
+    }
+
+    #[test]
+    fn infinity_and_nan_literals() {
+        let source = "def f(x; k) = switch x { inf -> nan | k, _ -> -inf | k }";
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let StatementKind::Switch(switch) = &program.definitions[0].body.kind else {
+            panic!("expected a switch");
+        };
+        assert!(matches!(
+            &switch.branches[0].kind,
+            BranchKind::LiteralBranch(LiteralBranch {
+                literal: Literal::Float(value),
+                ..
+            }) if *value == f64::INFINITY
+        ));
+    }
+
+    #[test]
+    fn declarations() {
+        let source =
+            "data List[a] { Nil(;), Cons(x: a, xs: List[a];) }\ncodata Stream[a] { Head(; k: a) }";
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let shapes: Vec<_> = program
+            .declarations
+            .iter()
+            .map(|declaration| {
+                let signatures: Vec<_> = declaration
+                    .signatures
+                    .iter()
+                    .map(|signature| {
+                        (
+                            signature.tag.as_str(),
+                            signature.parameters.len(),
+                            signature.returns.len(),
+                        )
+                    })
+                    .collect();
+                (declaration.kind, declaration.name.as_str(), signatures)
+            })
+            .collect();
+        assert_eq!(
+            shapes,
+            [
+                (
+                    DeclarationKind::Data,
+                    "List",
+                    vec![("Nil", 0, 0), ("Cons", 2, 0)]
+                ),
+                (DeclarationKind::Codata, "Stream", vec![("Head", 0, 1)]),
+            ]
+        );
+        assert_eq!(program.declarations[0].parameters, ["a"]);
+        assert_eq!(
+            text(source, &program.declarations[1].signatures[0].location),
+            "Head(; k: a)"
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+
+use crate::arity::Arity;
+use crate::core_ir::*;
+use crate::location;
+use crate::name::Name;
+use crate::printer;
+use crate::suggest;
+use crate::visit::{self, Visitor};
+use ariadne::Label;
+use ariadne::Report;
+use ariadne::ReportKind;
+use thiserror::Error;
+
+/// Runs a primitive on the literals it is given.
+/// Returns the index of the consumer to pass the result to, with the result,
+/// or a message if the primitive fails.
+pub type Run = fn(&[Literal]) -> Result<(usize, Literal), String>;
+
+/// A primitive operation available as `prim[name]`.
+#[derive(Debug, Clone)]
+pub struct Primitive {
+    pub name: String,
+    /// Types of the producers it takes. Type variables make it polymorphic.
+    pub parameters: Vec<Type>,
+    /// Types of the producers passed to the consumers it takes.
+    pub returns: Vec<Type>,
+    /// Whether it has no effect besides passing its result on,
+    /// so that it may be skipped or repeated freely.
+    pub pure: bool,
+    pub run: Run,
+}
+
+impl Primitive {
+    pub fn arity(&self) -> Arity {
+        Arity {
+            producers: self.parameters.len(),
+            consumers: self.returns.len(),
+        }
+    }
+}
+
+fn ty(name: &str) -> Type {
+    Type::Named(name.to_string(), Vec::new())
+}
+
+fn unexpected(arguments: &[Literal]) -> String {
+    let arguments = arguments
+        .iter()
+        .map(printer::literal)
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("unexpected arguments ({})", arguments)
+}
+
+fn int(arguments: &[Literal], op: fn(i64, i64) -> Option<i64>) -> Result<(usize, Literal), String> {
+    match arguments {
+        [Literal::Int(x), Literal::Int(y)] => match op(*x, *y) {
+            Some(z) => Ok((0, Literal::Int(z))),
+            // Only division and remainder fail on a zero right operand.
+            None if *y == 0 => Err("division by zero".to_string()),
+            None => Err("integer overflow".to_string()),
+        },
+        _ => Err(unexpected(arguments)),
+    }
+}
+
+fn int_comparison(
+    arguments: &[Literal],
+    op: fn(&i64, &i64) -> bool,
+) -> Result<(usize, Literal), String> {
+    match arguments {
+        [Literal::Int(x), Literal::Int(y)] => Ok((0, Literal::Bool(op(x, y)))),
+        _ => Err(unexpected(arguments)),
+    }
+}
+
+fn float(arguments: &[Literal], op: fn(f64, f64) -> f64) -> Result<(usize, Literal), String> {
+    match arguments {
+        [Literal::Float(x), Literal::Float(y)] => Ok((0, Literal::Float(op(*x, *y)))),
+        _ => Err(unexpected(arguments)),
+    }
+}
+
+fn float_comparison(
+    arguments: &[Literal],
+    op: fn(&f64, &f64) -> bool,
+) -> Result<(usize, Literal), String> {
+    match arguments {
+        [Literal::Float(x), Literal::Float(y)] => Ok((0, Literal::Bool(op(x, y)))),
+        _ => Err(unexpected(arguments)),
+    }
+}
+
+/// The primitives known to a session.
+/// `Registry::default` has the standard primitives; embedders may `register` more.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    primitives: BTreeMap<String, Primitive>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        let mut standard = |name: &str, parameters: &[&str], returns: &[&str], run: Run| {
+            registry.register(Primitive {
+                name: name.to_string(),
+                parameters: parameters.iter().map(|name| ty(name)).collect(),
+                returns: returns.iter().map(|name| ty(name)).collect(),
+                pure: true,
+                run,
+            });
+        };
+        let ints = &["Int", "Int"];
+        let floats = &["Float", "Float"];
+        standard("add", ints, &["Int"], |a| int(a, i64::checked_add));
+        standard("sub", ints, &["Int"], |a| int(a, i64::checked_sub));
+        standard("mul", ints, &["Int"], |a| int(a, i64::checked_mul));
+        standard("div", ints, &["Int"], |a| int(a, i64::checked_div));
+        standard("rem", ints, &["Int"], |a| int(a, i64::checked_rem));
+        standard("eq", ints, &["Bool"], |a| int_comparison(a, i64::eq));
+        standard("ne", ints, &["Bool"], |a| int_comparison(a, i64::ne));
+        standard("lt", ints, &["Bool"], |a| int_comparison(a, i64::lt));
+        standard("le", ints, &["Bool"], |a| int_comparison(a, i64::le));
+        standard("gt", ints, &["Bool"], |a| int_comparison(a, i64::gt));
+        standard("ge", ints, &["Bool"], |a| int_comparison(a, i64::ge));
+        standard("fadd", floats, &["Float"], |a| float(a, |x, y| x + y));
+        standard("fsub", floats, &["Float"], |a| float(a, |x, y| x - y));
+        standard("fmul", floats, &["Float"], |a| float(a, |x, y| x * y));
+        standard("fdiv", floats, &["Float"], |a| float(a, |x, y| x / y));
+        standard("feq", floats, &["Bool"], |a| float_comparison(a, f64::eq));
+        standard("flt", floats, &["Bool"], |a| float_comparison(a, f64::lt));
+        standard("fle", floats, &["Bool"], |a| float_comparison(a, f64::le));
+        standard("concat", &["String", "String"], &["String"], |a| match a {
+            [Literal::String(x), Literal::String(y)] => Ok((0, Literal::String(x.clone() + y))),
+            _ => Err(unexpected(a)),
+        });
+        standard("show", &["Int"], &["String"], |a| match a {
+            [Literal::Int(x)] => Ok((0, Literal::String(x.to_string()))),
+            _ => Err(unexpected(a)),
+        });
+        registry.register(Primitive {
+            name: "print".to_string(),
+            parameters: vec![ty("String")],
+            returns: vec![ty("String")],
+            pure: false,
+            run: |a| match a {
+                [Literal::String(x)] => {
+                    println!("{}", x);
+                    Ok((0, Literal::String(x.clone())))
+                }
+                _ => Err(unexpected(a)),
+            },
+        });
+        registry
+    }
+}
+
+impl Registry {
+    /// A registry without any primitives.
+    pub fn empty() -> Self {
+        Self {
+            primitives: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a primitive, returning the one it replaces, if any.
+    pub fn register(&mut self, primitive: Primitive) -> Option<Primitive> {
+        self.primitives.insert(primitive.name.clone(), primitive)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Primitive> {
+        self.primitives.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Primitive> {
+        self.primitives.values()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unknown primitive `{name}`")]
+    Unknown {
+        name: String,
+        location: location::Location,
+        /// A similarly-named primitive.
+        similar: Option<String>,
+    },
+    #[error("primitive `{name}` takes {expected}, but {actual} are given")]
+    Mismatch {
+        name: String,
+        expected: Arity,
+        actual: Arity,
+        location: location::Location,
+    },
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::Unknown {
+                location, similar, ..
+            } => {
+                let mut report = Report::build(ReportKind::Error, location.clone())
+                    .with_message(message)
+                    .with_label(Label::new(location).with_message("not a known primitive"));
+                if let Some(similar) = similar {
+                    report = report.with_help(format!("did you mean `{}`?", similar));
+                }
+                report.finish()
+            }
+            Error::Mismatch {
+                actual, location, ..
+            } => Report::build(ReportKind::Error, location.clone())
+                .with_message(message)
+                .with_label(Label::new(location).with_message(format!("{} given here", actual)))
+                .finish(),
+        }
+    }
+}
+
+/// Checks every `prim` statement against the registry.
+pub struct Checker<'a> {
+    registry: &'a Registry,
+    errors: Vec<Error>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(registry: &'a Registry) -> Self {
+        Self {
+            registry,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn check(mut self, program: &Program<Name>) -> Vec<Error> {
+        for definition in &program.definitions {
+            self.statement(&definition.body);
+        }
+        self.errors
+    }
+}
+
+impl<'p> Visitor<'p, Name> for Checker<'_> {
+    fn statement(&mut self, statement: &'p Statement<Name>) {
+        if let StatementKind::Prim(Prim {
+            name,
+            producers,
+            consumers,
+        }) = &statement.kind
+        {
+            let actual = Arity {
+                producers: producers.len(),
+                consumers: consumers.len(),
+            };
+            match self.registry.get(name) {
+                Some(primitive) if primitive.arity() != actual => {
+                    self.errors.push(Error::Mismatch {
+                        name: name.clone(),
+                        expected: primitive.arity(),
+                        actual,
+                        location: statement.location.clone(),
+                    })
+                }
+                Some(_) => {}
+                None => self.errors.push(Error::Unknown {
+                    name: name.clone(),
+                    location: statement.location.clone(),
+                    similar: suggest::closest(
+                        name,
+                        self.registry
+                            .iter()
+                            .map(|primitive| (primitive.name.as_str(), primitive.name.clone())),
+                    ),
+                }),
+            }
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::tests::program;
+
+    fn run(name: &str, arguments: &[Literal]) -> Result<(usize, Literal), String> {
+        let registry = Registry::default();
+        (registry.get(name).unwrap().run)(arguments)
+    }
+
+    fn check(source: &str) -> Vec<Error> {
+        Checker::new(&Registry::default()).check(&program(source))
+    }
+
+    #[test]
+    fn standard_primitives() {
+        let ints = |x, y| [Literal::Int(x), Literal::Int(y)];
+        assert!(matches!(run("add", &ints(2, 3)), Ok((0, Literal::Int(5)))));
+        assert!(matches!(run("rem", &ints(7, 3)), Ok((0, Literal::Int(1)))));
+        assert!(matches!(
+            run("lt", &ints(2, 3)),
+            Ok((0, Literal::Bool(true)))
+        ));
+        assert_eq!(run("div", &ints(1, 0)).unwrap_err(), "division by zero");
+        assert_eq!(
+            run("mul", &ints(i64::MAX, 2)).unwrap_err(),
+            "integer overflow"
+        );
+        assert!(matches!(
+            run("fdiv", &[Literal::Float(1.0), Literal::Float(4.0)]),
+            Ok((0, Literal::Float(x))) if x == 0.25
+        ));
+        assert!(matches!(
+            run("show", &[Literal::Int(-4)]),
+            Ok((0, Literal::String(s))) if s == "-4"
+        ));
+        assert_eq!(
+            run(
+                "concat",
+                &[Literal::String("a".to_string()), Literal::Int(1)]
+            )
+            .unwrap_err(),
+            "unexpected arguments (\"a\", 1)"
+        );
+    }
+
+    #[test]
+    fn only_print_is_impure() {
+        let registry = Registry::default();
+        let impure: Vec<_> = registry
+            .iter()
+            .filter(|primitive| !primitive.pure)
+            .map(|primitive| primitive.name.as_str())
+            .collect();
+        assert_eq!(impure, ["print"]);
+    }
+
+    #[test]
+    fn registering_replaces() {
+        let mut registry = Registry::empty();
+        assert!(registry.get("add").is_none());
+        let add = Registry::default().get("add").unwrap().clone();
+        assert!(registry.register(add.clone()).is_none());
+        assert!(registry.register(add).is_some());
+        assert_eq!(registry.iter().count(), 1);
+    }
+
+    #[test]
+    fn unknown_primitives_and_arities() {
+        let errors = check("def f(; k) = prim[ad](1, 2; k)\ndef g(; k) = prim[add](1; k)");
+        let [Error::Unknown { name, similar, .. }, Error::Mismatch {
+            expected, actual, ..
+        }] = &errors[..]
+        else {
+            panic!(
+                "expected an unknown primitive and a mismatch, got {:?}",
+                errors
+            );
+        };
+        assert_eq!(name, "ad");
+        assert_eq!(similar.as_deref(), Some("add"));
+        assert_eq!((expected.producers, actual.producers), (2, 1));
+    }
+}
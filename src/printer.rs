@@ -0,0 +1,823 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+use crate::core_ir::*;
+use crate::{lexer, location, parser, token};
+
+/// Default line width of the printer.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Indentation of nested blocks.
+const INDENT: usize = 4;
+
+/// A document in the style of Wadler's "A prettier printer".
+/// Every `Group` is laid out on a single line if it fits in the remaining width,
+/// otherwise its `Line`s become line breaks.
+#[derive(Debug, Clone)]
+pub enum Doc {
+    Text(String),
+    /// A space if the enclosing group is flat, otherwise a line break.
+    Line,
+    /// Nothing if the enclosing group is flat, otherwise a line break.
+    SoftLine,
+    /// Always a line break. The enclosing groups are never flat.
+    HardLine,
+    /// The first document if the enclosing group is broken, otherwise the second.
+    IfBreak(Box<Doc>, Box<Doc>),
+    /// Text deferred to the end of the line, such as a trailing line comment.
+    /// The enclosing groups are not flat if a line break follows it within them.
+    LineSuffix(String),
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    pub fn nest(self, indent: usize) -> Self {
+        Doc::Nest(indent, Box::new(self))
+    }
+
+    pub fn group(self) -> Self {
+        Doc::Group(Box::new(self))
+    }
+
+    /// Concatenates `docs`, putting `separator` between each of them.
+    pub fn join(docs: impl IntoIterator<Item = Doc>, separator: Doc) -> Self {
+        let mut result = Vec::new();
+        for doc in docs {
+            if !result.is_empty() {
+                result.push(separator.clone());
+            }
+            result.push(doc);
+        }
+        Doc::Concat(result)
+    }
+
+    /// Renders the document, breaking lines to fit in `width` columns where possible.
+    pub fn render(&self, width: usize) -> String {
+        let mut output = String::new();
+        let mut column = 0;
+        let mut suffixes = Vec::new();
+        let mut stack = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    output.push_str(text);
+                    column = match text.rfind('\n') {
+                        Some(newline) => text[newline + 1..].chars().count(),
+                        None => column + text.chars().count(),
+                    };
+                }
+                Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                    if let Doc::Line = doc {
+                        output.push(' ');
+                        column += 1;
+                    }
+                }
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    // Do not leave trailing whitespace on the broken line.
+                    while output.ends_with(' ') {
+                        output.pop();
+                    }
+                    output.extend(suffixes.drain(..));
+                    output.push('\n');
+                    output.extend(std::iter::repeat(' ').take(indent));
+                    column = indent;
+                }
+                Doc::IfBreak(broken, flat) => {
+                    stack.push((
+                        indent,
+                        mode,
+                        if mode == Mode::Break { broken } else { flat },
+                    ));
+                }
+                Doc::LineSuffix(text) => suffixes.push(text.as_str()),
+                Doc::Nest(i, doc) => stack.push((indent + i, mode, doc)),
+                Doc::Group(doc) => {
+                    let mode =
+                        if mode == Mode::Flat || fits(width.saturating_sub(column), doc, &stack) {
+                            Mode::Flat
+                        } else {
+                            Mode::Break
+                        };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+                }
+            }
+        }
+
+        output.extend(suffixes);
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Returns true if `doc` laid out flat, followed by `rest` up to its next line break,
+/// fits in `width` columns.
+fn fits(width: usize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut remaining = width as isize;
+    let mut suffix = false;
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            // A line suffix must stay at the end of its line.
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat && suffix => return false,
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::HardLine if mode == Mode::Flat => return false,
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::IfBreak(broken, flat) => {
+                stack.push((mode, if mode == Mode::Break { broken } else { flat }))
+            }
+            Doc::LineSuffix(_) => suffix = true,
+            Doc::Nest(_, doc) => stack.push((mode, doc)),
+            Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+        }
+        if remaining < 0 {
+            return false;
+        }
+    }
+}
+
+/// A comment of the source, with where it belongs in the program.
+struct Comment {
+    location: location::Location,
+    kind: token::CommentKind,
+    text: String,
+    /// The end of the token the comment follows on the same line, if any.
+    /// Separators are skipped for a comment that ends its line,
+    /// so in `x, // c` the comment follows `x`.
+    after: Option<usize>,
+    /// Whether nothing but whitespace follows the comment on its line.
+    ends_line: bool,
+}
+
+/// Finds where each of the `comments` of `source` belongs.
+fn attach(source: &str, comments: Vec<token::Comment>) -> VecDeque<Comment> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut attached = Vec::new();
+    for comment in comments {
+        let ends_line = chars[comment.location.end..]
+            .iter()
+            .find(|c| !matches!(c, ' ' | '\t' | '\r'))
+            .is_none_or(|&c| c == '\n');
+        let after = if comment.newline_before {
+            None
+        } else {
+            token_before(&chars, &attached, comment.location.start, ends_line)
+        };
+        attached.push(Comment {
+            location: comment.location,
+            kind: comment.kind,
+            text: comment.text,
+            after,
+            ends_line,
+        });
+    }
+    attached.into()
+}
+
+/// Returns the end of the last token before `position` on its line,
+/// or of the last one other than `,` and `;` if `skip_separators` is set.
+/// `earlier` are the comments before `position`.
+fn token_before(
+    chars: &[char],
+    earlier: &[Comment],
+    mut position: usize,
+    skip_separators: bool,
+) -> Option<usize> {
+    let mut earlier = earlier.iter().rev().peekable();
+    loop {
+        while position > 0 && chars[position - 1] != '\n' && chars[position - 1].is_whitespace() {
+            position -= 1;
+        }
+        if let Some(comment) = earlier.next_if(|comment| comment.location.end == position) {
+            if comment.text.contains('\n') {
+                return None;
+            }
+            position = comment.location.start;
+            continue;
+        }
+        match chars[..position].last()? {
+            '\n' => return None,
+            ',' | ';' if skip_separators => position -= 1,
+            _ => return Some(position),
+        }
+    }
+}
+
+/// Prints core IR in the surface syntax accepted by `parser::Parser`.
+///
+/// Comments given to the printer are emitted in front of the first node that
+/// starts after them, or at the end of the line of the node they follow.
+/// Literals keep their spelling in the source given to the printer, if any.
+pub struct Printer {
+    width: usize,
+    comments: VecDeque<Comment>,
+    source: String,
+    /// The byte offset of every character of `source`.
+    offsets: Vec<usize>,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH)
+    }
+}
+
+impl Printer {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            comments: VecDeque::new(),
+            source: String::new(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Creates a printer for a program parsed from `source`,
+    /// that interleaves its `comments` with the program.
+    pub fn with_source(width: usize, source: &str, comments: Vec<token::Comment>) -> Self {
+        Self {
+            width,
+            comments: attach(source, comments),
+            source: source.to_string(),
+            offsets: source.char_indices().map(|(offset, _)| offset).collect(),
+        }
+    }
+
+    pub fn print<N: Display>(&mut self, program: &Program<N>) -> String {
+        let mut output = self.program(program).render(self.width);
+        output.push('\n');
+        output
+    }
+
+    fn program<N: Display>(&mut self, program: &Program<N>) -> Doc {
+        // Declarations and definitions are kept apart in the program,
+        // so they are merged back into source order to keep comments in place.
+        let mut declarations = program.declarations.iter().peekable();
+        let mut definitions = program.definitions.iter().peekable();
+        let mut items = Vec::new();
+        loop {
+            let item = match (declarations.peek(), definitions.peek()) {
+                (Some(declaration), Some(definition))
+                    if declaration.location.start <= definition.location.start =>
+                {
+                    self.declaration(declarations.next().unwrap())
+                }
+                (_, Some(_)) => self.definition(definitions.next().unwrap()),
+                (Some(_), None) => self.declaration(declarations.next().unwrap()),
+                (None, None) => break,
+            };
+            items.push(item);
+        }
+        if !self.comments.is_empty() {
+            let comments = self
+                .comments
+                .drain(..)
+                .map(|comment| Doc::text(comment.text));
+            items.push(Doc::join(comments, Doc::HardLine));
+        }
+        Doc::join(items, Doc::Concat(vec![Doc::HardLine, Doc::HardLine]))
+    }
+
+    /// Surrounds the document of the node at `location` with its comments.
+    fn commented(
+        &mut self,
+        location: &location::Location,
+        node: impl FnOnce(&mut Self) -> Doc,
+    ) -> Doc {
+        let leading = self.leading_comments(location);
+        let node = node(self);
+        let trailing = self.trailing_comments(location);
+        Doc::Concat(vec![leading, node, trailing])
+    }
+
+    /// Emits the comments that start before `location`.
+    /// Each is followed by a line break, unless code followed it on its line.
+    fn leading_comments(&mut self, location: &location::Location) -> Doc {
+        let mut docs = Vec::new();
+        while let Some(comment) = self.comments.front() {
+            if comment.location.start >= location.start {
+                break;
+            }
+            let comment = self.comments.pop_front().unwrap();
+            let inline = comment.kind == token::CommentKind::Block && !comment.ends_line;
+            docs.push(Doc::text(comment.text));
+            docs.push(if inline {
+                Doc::text(" ")
+            } else {
+                Doc::HardLine
+            });
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Emits the comments left inside the node at `location`,
+    /// and the comments that follow it on its line.
+    fn trailing_comments(&mut self, location: &location::Location) -> Doc {
+        let mut docs = Vec::new();
+        while let Some(comment) = self.comments.front() {
+            if comment.location.start >= location.end && comment.after != Some(location.end) {
+                break;
+            }
+            let comment = self.comments.pop_front().unwrap();
+            let text = format!(" {}", comment.text);
+            docs.push(if comment.ends_line {
+                Doc::LineSuffix(text)
+            } else {
+                Doc::Text(text)
+            });
+        }
+        Doc::Concat(docs)
+    }
+
+    /// Prints a literal as spelled at `start` in the source,
+    /// or in its canonical form if the source does not spell it there.
+    fn literal(&self, literal: &Literal, start: usize) -> String {
+        let canonical = self::literal(literal);
+        let Some(&offset) = self.offsets.get(start) else {
+            return canonical;
+        };
+        let rest = &self.source[offset..];
+        match lexer::Lexer::new(String::new(), rest).next_token() {
+            Some(Ok(token::Token {
+                kind: token::TokenKind::Literal(spelled),
+                location,
+            })) if self::literal(&spelled) == canonical => {
+                rest.chars().take(location.end).collect()
+            }
+            _ => canonical,
+        }
+    }
+
+    fn declaration(&mut self, declaration: &Declaration) -> Doc {
+        let keyword = match declaration.kind {
+            DeclarationKind::Data => "data",
+            DeclarationKind::Codata => "codata",
+        };
+        self.commented(&declaration.location, |printer| {
+            let signatures = declaration
+                .signatures
+                .iter()
+                .map(|signature| printer.signature(signature))
+                .collect::<Vec<_>>();
+            Doc::Concat(vec![
+                Doc::text(format!("{} {}", keyword, declaration.name)),
+                if declaration.parameters.is_empty() {
+                    Doc::Concat(vec![])
+                } else {
+                    Doc::text(format!("[{}]", declaration.parameters.join(", ")))
+                },
+                block(signatures),
+            ])
+        })
+    }
+
+    fn signature(&mut self, signature: &Signature) -> Doc {
+        self.commented(&signature.location, |printer| {
+            Doc::Concat(vec![
+                Doc::text(signature.tag.clone()),
+                printer.binders(&signature.parameters, &signature.returns),
+            ])
+        })
+    }
+
+    fn definition<N: Display>(&mut self, definition: &Definition<N>) -> Doc {
+        self.commented(&definition.location, |printer| {
+            Doc::Concat(vec![
+                Doc::Concat(
+                    definition
+                        .attributes
+                        .iter()
+                        .flat_map(|attribute| [Doc::text(attribute.to_string()), Doc::HardLine])
+                        .collect(),
+                ),
+                Doc::Concat(vec![
+                    Doc::text(format!("def {}", definition.name)),
+                    printer.binders(&definition.parameters, &definition.returns),
+                    Doc::text(" ="),
+                    Doc::Concat(vec![Doc::Line, printer.statement(&definition.body)]).nest(INDENT),
+                ])
+                .group(),
+            ])
+        })
+    }
+
+    fn statement<N: Display>(&mut self, statement: &Statement<N>) -> Doc {
+        self.commented(&statement.location, |printer| match &statement.kind {
+            StatementKind::Cut(Cut { producer, consumer }) => Doc::Concat(vec![
+                printer.producer(producer),
+                Doc::text(" | "),
+                printer.consumer(consumer),
+            ]),
+            StatementKind::Prim(Prim {
+                name,
+                producers,
+                consumers,
+            }) => Doc::Concat(vec![
+                Doc::text(format!("prim[{}]", name)),
+                printer.arguments(producers, consumers),
+            ]),
+            StatementKind::Switch(Switch {
+                scrutinee,
+                branches,
+            }) => {
+                let branches = branches
+                    .iter()
+                    .map(|branch| printer.branch(branch))
+                    .collect::<Vec<_>>();
+                Doc::Concat(vec![
+                    Doc::text("switch "),
+                    printer.producer(scrutinee),
+                    block(branches),
+                ])
+            }
+            StatementKind::Invoke(Invoke {
+                name,
+                producers,
+                consumers,
+            }) => Doc::Concat(vec![
+                Doc::text(format!("invoke[{}]", name)),
+                printer.arguments(producers, consumers),
+            ]),
+            StatementKind::Error => Doc::text("<error>"),
+        })
+    }
+
+    fn producer<N: Display>(&mut self, producer: &Producer<N>) -> Doc {
+        self.commented(&producer.location, |printer| match &producer.kind {
+            ProducerKind::Variable(Variable { name }) => Doc::text(name.to_string()),
+            ProducerKind::Literal(literal) => {
+                Doc::text(printer.literal(literal, producer.location.start))
+            }
+            ProducerKind::Do(Do { binder, body }) => {
+                let binder = printer.binder(binder);
+                bound("do", binder, printer.statement(body))
+            }
+            ProducerKind::Construct(Construct {
+                tag,
+                producers,
+                consumers,
+            }) => Doc::Concat(vec![
+                Doc::text(tag.clone()),
+                printer.arguments(producers, consumers),
+            ]),
+            ProducerKind::Comatch(Comatch { clauses }) => {
+                let clauses = clauses
+                    .iter()
+                    .map(|clause| printer.coclause(clause))
+                    .collect::<Vec<_>>();
+                Doc::Concat(vec![Doc::text("comatch"), block(clauses)])
+            }
+            ProducerKind::Error => Doc::text("<error>"),
+        })
+    }
+
+    fn consumer<N: Display>(&mut self, consumer: &Consumer<N>) -> Doc {
+        self.commented(&consumer.location, |printer| match &consumer.kind {
+            ConsumerKind::Finish => Doc::text("finish"),
+            ConsumerKind::Variable(Variable { name }) => Doc::text(name.to_string()),
+            ConsumerKind::Then(Then { binder, body }) => {
+                let binder = printer.binder(binder);
+                bound("then", binder, printer.statement(body))
+            }
+            ConsumerKind::Destruct(Destruct {
+                tag,
+                producers,
+                consumers,
+            }) => Doc::Concat(vec![
+                Doc::text(tag.clone()),
+                printer.arguments(producers, consumers),
+            ]),
+            ConsumerKind::Match(Match { clauses }) => {
+                let clauses = clauses
+                    .iter()
+                    .map(|clause| printer.clause(clause))
+                    .collect::<Vec<_>>();
+                Doc::Concat(vec![Doc::text("match"), block(clauses)])
+            }
+            ConsumerKind::Error => Doc::text("<error>"),
+        })
+    }
+
+    fn clause<N: Display>(&mut self, clause: &Clause<N>) -> Doc {
+        let Pattern {
+            tag,
+            parameters,
+            returns,
+        } = &clause.pattern;
+        self.commented(&clause.location, |printer| {
+            Doc::Concat(vec![
+                Doc::text(tag.clone()),
+                printer.binders(parameters, returns),
+                Doc::text(" -> "),
+                printer.statement(&clause.body),
+            ])
+        })
+    }
+
+    fn coclause<N: Display>(&mut self, coclause: &Coclause<N>) -> Doc {
+        let Copattern {
+            tag,
+            parameters,
+            returns,
+        } = &coclause.copattern;
+        self.commented(&coclause.location, |printer| {
+            Doc::Concat(vec![
+                Doc::text(tag.clone()),
+                printer.binders(parameters, returns),
+                Doc::text(" -> "),
+                printer.statement(&coclause.body),
+            ])
+        })
+    }
+
+    fn branch<N: Display>(&mut self, branch: &Branch<N>) -> Doc {
+        self.commented(&branch.location, |printer| {
+            let (pattern, body) = match &branch.kind {
+                BranchKind::LiteralBranch(LiteralBranch { literal, body }) => {
+                    (printer.literal(literal, branch.location.start), body)
+                }
+                BranchKind::DefaultBranch(body) => ("_".to_string(), body),
+            };
+            Doc::Concat(vec![
+                Doc::text(pattern),
+                Doc::text(" -> "),
+                printer.statement(body),
+            ])
+        })
+    }
+
+    /// Prints `(x: T, y; k)` for the binders of a definition, signature or pattern.
+    fn binders<N: Display>(&mut self, parameters: &[Binder<N>], returns: &[Binder<N>]) -> Doc {
+        let parameters = parameters
+            .iter()
+            .map(|binder| self.binder(binder))
+            .collect();
+        let returns = returns.iter().map(|binder| self.binder(binder)).collect();
+        arguments(parameters, returns)
+    }
+
+    /// Prints `x` or `x: T`.
+    fn binder<N: Display>(&mut self, binder: &Binder<N>) -> Doc {
+        self.commented(&binder.location, |_| match &binder.ty {
+            Some(ty) => Doc::text(format!("{}: {}", binder.name, ty)),
+            None => Doc::text(binder.name.to_string()),
+        })
+    }
+
+    /// Prints `(p1, p2; c1, c2)`, breaking one argument per line if it does not fit.
+    fn arguments<N: Display>(
+        &mut self,
+        producers: &[Producer<N>],
+        consumers: &[Consumer<N>],
+    ) -> Doc {
+        let producers = producers
+            .iter()
+            .map(|producer| self.producer(producer))
+            .collect::<Vec<_>>();
+        let consumers = consumers
+            .iter()
+            .map(|consumer| self.consumer(consumer))
+            .collect::<Vec<_>>();
+        arguments(producers, consumers)
+    }
+}
+
+/// Formats a source file in the canonical layout, keeping its comments.
+/// Returns the parse errors instead if the file does not parse.
+pub fn format(
+    file: String,
+    source: &str,
+    width: usize,
+) -> std::result::Result<String, Vec<parser::Error>> {
+    let mut parser = parser::Parser::new(lexer::Lexer::new(file, source));
+    let (program, errors) = parser.parse();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Printer::with_source(width, source, parser.into_comments()).print(&program))
+}
+
+fn arguments(producers: Vec<Doc>, consumers: Vec<Doc>) -> Doc {
+    let separator = Doc::Concat(vec![Doc::text(","), Doc::Line]);
+    let has_consumers = !consumers.is_empty();
+    let mut items = vec![Doc::join(producers, separator.clone()), Doc::text(";")];
+    if has_consumers {
+        items.push(Doc::Line);
+        items.push(Doc::join(consumers, separator));
+        items.push(Doc::IfBreak(
+            Box::new(Doc::text(",")),
+            Box::new(Doc::text("")),
+        ));
+    }
+    Doc::Concat(vec![
+        Doc::text("("),
+        Doc::Concat(vec![Doc::SoftLine, Doc::Concat(items)]).nest(INDENT),
+        Doc::SoftLine,
+        Doc::text(")"),
+    ])
+    .group()
+}
+
+/// Prints `keyword binder body`, moving the body to the next line if it does not fit.
+fn bound(keyword: &str, binder: Doc, body: Doc) -> Doc {
+    Doc::Concat(vec![
+        Doc::text(format!("{} ", keyword)),
+        binder,
+        Doc::Concat(vec![Doc::Line, body]).nest(INDENT),
+    ])
+    .group()
+}
+
+/// Prints ` { item, item }`, or one item per line with trailing commas if it does not fit.
+fn block(items: Vec<Doc>) -> Doc {
+    if items.is_empty() {
+        return Doc::text(" {}");
+    }
+    let separator = Doc::Concat(vec![Doc::text(","), Doc::Line]);
+    Doc::Concat(vec![
+        Doc::text(" {"),
+        Doc::Concat(vec![
+            Doc::Line,
+            Doc::join(items, separator),
+            Doc::IfBreak(Box::new(Doc::text(",")), Box::new(Doc::text(""))),
+        ])
+        .nest(INDENT),
+        Doc::Line,
+        Doc::text("}"),
+    ])
+    .group()
+}
+
+/// Prints a literal so that the parser reads it back as the same value.
+pub fn literal(literal: &Literal) -> String {
+    match literal {
+        Literal::Int(value) => value.to_string(),
+        Literal::Float(value) if value.is_nan() => "nan".to_string(),
+        // `{:?}` always prints a fraction or an exponent, and prints infinities as `inf`.
+        Literal::Float(value) => format!("{:?}", value),
+        Literal::Bool(value) => value.to_string(),
+        Literal::String(value) => {
+            let mut output = String::from("\"");
+            for c in value.chars() {
+                match c {
+                    '\n' => output.push_str("\\n"),
+                    '\t' => output.push_str("\\t"),
+                    '\r' => output.push_str("\\r"),
+                    '\0' => output.push_str("\\0"),
+                    '\\' => output.push_str("\\\\"),
+                    '"' => output.push_str("\\\""),
+                    c if c.is_control() => output.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                    c => output.push(c),
+                }
+            }
+            output.push('"');
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(file: &str, source: &str) -> Program<String> {
+        let (program, errors) =
+            parser::Parser::new(lexer::Lexer::new(file.to_string(), source)).parse();
+        assert!(errors.is_empty(), "{}: {:?}", file, errors);
+        program
+    }
+
+    /// Returns the path and contents of every example program.
+    fn examples() -> Vec<(String, String)> {
+        let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut examples: Vec<_> = std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "coco")
+            })
+            .map(|path| {
+                let source = std::fs::read_to_string(&path).unwrap();
+                (path.display().to_string(), source)
+            })
+            .collect();
+        examples.sort();
+        assert!(!examples.is_empty());
+        examples
+    }
+
+    #[test]
+    fn examples_round_trip() {
+        for width in [DEFAULT_WIDTH, 20] {
+            for (file, source) in examples() {
+                let printed = Printer::new(width).print(&parse(&file, &source));
+                let reprinted = Printer::new(width).print(&parse(&file, &printed));
+                assert_eq!(printed, reprinted, "{} at width {}", file, width);
+            }
+        }
+    }
+
+    #[test]
+    fn examples_are_formatted() {
+        for (file, source) in examples() {
+            let formatted = format(file.clone(), &source, DEFAULT_WIDTH).unwrap();
+            assert_eq!(formatted, source, "{}", file);
+        }
+    }
+
+    #[test]
+    fn breaks_long_lines() {
+        let source = "def f(x; k) = Cons(x, Cons(x, Cons(x, Nil;););) | k\n";
+        let printed = Printer::new(20).print(&parse("test.coco", source));
+        assert!(printed.lines().all(|line| line.len() <= 20), "{}", printed);
+        assert_eq!(
+            Printer::default().print(&parse("test.coco", &printed)),
+            source
+        );
+    }
+
+    fn formatted(source: &str) -> String {
+        format("test.coco".to_string(), source, DEFAULT_WIDTH).unwrap()
+    }
+
+    #[test]
+    fn comments_stay_in_place() {
+        let source = "\
+// header
+data List[a] {
+    Nil(;), // empty
+    Cons(x: a, xs: List[a];),
+} // after
+
+def g(; k) =
+    Cons(1, /* one */ Nil(;);) | match {
+        Nil(;) -> 1 | k, // nil
+        Cons(x, xs;) -> x /* head */ | k,
+    }
+
+// final
+";
+        assert_eq!(formatted(source), source);
+    }
+
+    #[test]
+    fn trailing_comments_stay_on_their_line() {
+        let source = "def f(x; k) = Cons(x, // first\n x;) | k // end\n";
+        let once = formatted(source);
+        assert_eq!(
+            once,
+            "def f(x; k) =\n    Cons(\n        x, // first\n        x;\n    ) | k // end\n"
+        );
+        assert_eq!(formatted(&once), once);
+    }
+
+    #[test]
+    fn comments_follow_the_token_before_them() {
+        let source = "x, /* a */ // b\n/* c */ y";
+        let mut lexer = lexer::Lexer::new("test.coco".to_string(), source);
+        lexer.by_ref().for_each(drop);
+        let attached: Vec<_> = attach(source, lexer.trivia)
+            .into_iter()
+            .map(|comment| (comment.after, comment.ends_line))
+            .collect();
+        assert_eq!(attached, [(Some(2), false), (Some(1), true), (None, false)]);
+    }
+
+    #[test]
+    fn literals_keep_their_spelling() {
+        let source = "def f(x; k) = switch x { 0xFF_FF -> 1_000_000 | k, _ -> r#\"a\\n\"# | k }\n";
+        assert_eq!(formatted(source), source);
+        let program = parse("test.coco", source);
+        assert_eq!(
+            Printer::default().print(&program),
+            "def f(x; k) = switch x { 65535 -> 1000000 | k, _ -> \"a\\\\n\" | k }\n"
+        );
+    }
+}
@@ -0,0 +1,651 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core_ir::*;
+use crate::location;
+use crate::name::{Name, Supply};
+use crate::suggest;
+use ariadne::Label;
+use ariadne::Report;
+use ariadne::ReportKind;
+use thiserror::Error;
+
+/// Producers refer to variables and consumers refer to covariables.
+/// The two live in separate namespaces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sort {
+    Variable,
+    Covariable,
+}
+
+impl Sort {
+    fn other(self) -> Sort {
+        match self {
+            Sort::Variable => Sort::Covariable,
+            Sort::Covariable => Sort::Variable,
+        }
+    }
+}
+
+impl std::fmt::Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Sort::Variable => write!(f, "variable"),
+            Sort::Covariable => write!(f, "covariable"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unbound {sort} `{name}`")]
+    UnboundVariable {
+        name: String,
+        sort: Sort,
+        location: location::Location,
+        /// A similarly-named binder of the same sort in scope and where it is bound.
+        similar: Option<(String, location::Location)>,
+    },
+    #[error("`{name}` is a {actual}, but a {} is expected here", .actual.other())]
+    SortMismatch {
+        name: String,
+        actual: Sort,
+        location: location::Location,
+        binder: location::Location,
+    },
+    #[error("unknown definition `{name}`")]
+    UnknownDefinition {
+        name: String,
+        location: location::Location,
+        /// A similarly-named definition and where it is defined.
+        similar: Option<(String, location::Location)>,
+    },
+    #[error("duplicate {what} `{name}`")]
+    Duplicate {
+        /// What is duplicated: a definition, a type, a constructor, a destructor,
+        /// or a variable or covariable bound twice by the same binder.
+        what: &'static str,
+        name: String,
+        location: location::Location,
+        previous: location::Location,
+    },
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::UnboundVariable {
+                location, similar, ..
+            } => similar_report(message, location, similar, "bound"),
+            Error::SortMismatch {
+                actual,
+                location,
+                binder,
+                ..
+            } => Report::build(ReportKind::Error, location.clone())
+                .with_message(message)
+                .with_label(
+                    Label::new(location).with_message(format!("expected a {}", actual.other())),
+                )
+                .with_label(Label::new(binder).with_message(format!("bound as a {} here", actual)))
+                .finish(),
+            Error::UnknownDefinition {
+                location, similar, ..
+            } => similar_report(message, location, similar, "defined"),
+            Error::Duplicate {
+                what,
+                location,
+                previous,
+                ..
+            } => {
+                let verb = match what {
+                    "variable" | "covariable" => "bound",
+                    _ => "defined",
+                };
+                Report::build(ReportKind::Error, location.clone())
+                    .with_message(message)
+                    .with_label(Label::new(location).with_message(format!("{} again here", verb)))
+                    .with_label(Label::new(previous).with_message(format!("first {} here", verb)))
+                    .finish()
+            }
+        }
+    }
+}
+
+/// Reports a name that is not in scope,
+/// pointing at the most similar name that is, if any.
+fn similar_report(
+    message: String,
+    location: location::Location,
+    similar: Option<(String, location::Location)>,
+    verb: &str,
+) -> Report<'static, location::Location> {
+    let mut report = Report::build(ReportKind::Error, location.clone())
+        .with_message(message)
+        .with_label(Label::new(location).with_message("not found in this scope"));
+    if let Some((name, binder)) = similar {
+        report = report.with_label(
+            Label::new(binder).with_message(format!("a similar name `{}` is {} here", name, verb)),
+        );
+    }
+    report.finish()
+}
+
+/// Resolves every name in a program to a unique `Name`.
+///
+/// Definitions are visible everywhere in the program, so they may be mutually recursive.
+/// Every other binder is visible in its body only, and shadows outer binders of the same name.
+pub struct Resolver<'a> {
+    names: &'a mut Supply,
+    definitions: BTreeMap<String, (Name, location::Location)>,
+    /// Binders in scope, innermost last, with their sort and location.
+    scope: Vec<(String, Sort, Name, location::Location)>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(names: &'a mut Supply) -> Self {
+        Self {
+            names,
+            definitions: BTreeMap::new(),
+            scope: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Resolves the whole program.
+    /// Every error is collected, and unresolved occurrences get a fresh `Name`
+    /// so that the returned program is complete.
+    pub fn resolve(&mut self, program: Program<String>) -> (Program<Name>, Vec<Error>) {
+        let Program {
+            declarations,
+            definitions,
+        } = program;
+        self.declarations(&declarations);
+
+        let mut names = Vec::new();
+        for definition in &definitions {
+            let name = self.names.fresh(definition.name.clone());
+            if let Some((_, previous)) = self.definitions.get(&definition.name) {
+                self.errors.push(Error::Duplicate {
+                    what: "definition",
+                    name: definition.name.clone(),
+                    location: definition.location.clone(),
+                    previous: previous.clone(),
+                });
+            } else {
+                self.definitions.insert(
+                    definition.name.clone(),
+                    (name.clone(), definition.location.clone()),
+                );
+            }
+            names.push(name);
+        }
+
+        let definitions = definitions
+            .into_iter()
+            .zip(names)
+            .map(|(definition, name)| self.definition(definition, name))
+            .collect();
+
+        let program = Program {
+            declarations,
+            definitions,
+        };
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    /// Reports types declared twice, and constructors or destructors declared twice.
+    /// Constructors share one namespace across all data types, and destructors across all codata types.
+    fn declarations(&mut self, declarations: &[Declaration]) {
+        let mut types = HashMap::new();
+        let mut tags = HashMap::new();
+        for declaration in declarations {
+            if let Some(previous) = types.insert(&declaration.name, &declaration.location) {
+                self.errors.push(Error::Duplicate {
+                    what: "type",
+                    name: declaration.name.clone(),
+                    location: declaration.location.clone(),
+                    previous: previous.clone(),
+                });
+            }
+            for signature in &declaration.signatures {
+                let key = (declaration.kind, &signature.tag);
+                if let Some(previous) = tags.insert(key, &signature.location) {
+                    self.errors.push(Error::Duplicate {
+                        what: match declaration.kind {
+                            DeclarationKind::Data => "constructor",
+                            DeclarationKind::Codata => "destructor",
+                        },
+                        name: signature.tag.clone(),
+                        location: signature.location.clone(),
+                        previous: previous.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn definition(&mut self, definition: Definition<String>, name: Name) -> Definition<Name> {
+        let Definition {
+            location,
+            attributes,
+            name: _,
+            parameters,
+            returns,
+            body,
+        } = definition;
+        let (parameters, returns, body) =
+            self.scoped(parameters, returns, |resolver| resolver.statement(body));
+        Definition {
+            location,
+            attributes,
+            name,
+            parameters,
+            returns,
+            body,
+        }
+    }
+
+    /// Binds `parameters` as variables and `returns` as covariables
+    /// while resolving `body`.
+    fn scoped<T>(
+        &mut self,
+        parameters: Vec<Binder<String>>,
+        returns: Vec<Binder<String>>,
+        body: impl FnOnce(&mut Self) -> T,
+    ) -> (Vec<Binder<Name>>, Vec<Binder<Name>>, T) {
+        self.duplicates(&parameters, Sort::Variable);
+        self.duplicates(&returns, Sort::Covariable);
+        let depth = self.scope.len();
+        let parameters = parameters
+            .into_iter()
+            .map(|binder| self.bind(binder, Sort::Variable))
+            .collect();
+        let returns = returns
+            .into_iter()
+            .map(|binder| self.bind(binder, Sort::Covariable))
+            .collect();
+        let body = body(self);
+        self.scope.truncate(depth);
+        (parameters, returns, body)
+    }
+
+    /// Reports names bound twice among `binders`.
+    fn duplicates(&mut self, binders: &[Binder<String>], sort: Sort) {
+        let mut seen = HashMap::new();
+        for binder in binders {
+            if let Some(previous) = seen.insert(&binder.name, &binder.location) {
+                self.errors.push(Error::Duplicate {
+                    what: match sort {
+                        Sort::Variable => "variable",
+                        Sort::Covariable => "covariable",
+                    },
+                    name: binder.name.clone(),
+                    location: binder.location.clone(),
+                    previous: previous.clone(),
+                });
+            }
+        }
+    }
+
+    fn bind(&mut self, binder: Binder<String>, sort: Sort) -> Binder<Name> {
+        let Binder { location, name, ty } = binder;
+        let fresh = self.names.fresh(name.clone());
+        self.scope
+            .push((name, sort, fresh.clone(), location.clone()));
+        Binder {
+            location,
+            name: fresh,
+            ty,
+        }
+    }
+
+    fn lookup(&mut self, text: String, sort: Sort, location: &location::Location) -> Name {
+        let mut binders = self.scope.iter().rev().filter(|(t, _, _, _)| *t == text);
+        if let Some((_, _, name, _)) = binders.clone().find(|(_, s, _, _)| *s == sort) {
+            return name.clone();
+        }
+        if let Some((_, actual, other, binder)) = binders.next() {
+            self.errors.push(Error::SortMismatch {
+                name: text,
+                actual: *actual,
+                location: location.clone(),
+                binder: binder.clone(),
+            });
+            return self.names.fresh_like(other);
+        }
+        let similar = suggest::closest(
+            &text,
+            self.scope
+                .iter()
+                .filter(|(_, s, _, _)| *s == sort)
+                .map(|(t, _, _, binder)| (t.as_str(), (t.clone(), binder.clone()))),
+        );
+        self.errors.push(Error::UnboundVariable {
+            name: text.clone(),
+            sort,
+            location: location.clone(),
+            similar,
+        });
+        self.names.fresh(text)
+    }
+
+    fn lookup_definition(&mut self, text: String, location: &location::Location) -> Name {
+        if let Some((name, _)) = self.definitions.get(&text) {
+            return name.clone();
+        }
+        let similar = suggest::closest(
+            &text,
+            self.definitions
+                .iter()
+                .map(|(t, (_, definition))| (t.as_str(), (t.clone(), definition.clone()))),
+        );
+        self.errors.push(Error::UnknownDefinition {
+            name: text.clone(),
+            location: location.clone(),
+            similar,
+        });
+        self.names.fresh(text)
+    }
+
+    fn statement(&mut self, statement: Statement<String>) -> Statement<Name> {
+        let Statement { location, kind } = statement;
+        let kind = match kind {
+            StatementKind::Cut(Cut { producer, consumer }) => StatementKind::Cut(Cut {
+                producer: self.producer(producer),
+                consumer: self.consumer(consumer),
+            }),
+            StatementKind::Prim(Prim {
+                name,
+                producers,
+                consumers,
+            }) => StatementKind::Prim(Prim {
+                name,
+                producers: self.producers(producers),
+                consumers: self.consumers(consumers),
+            }),
+            StatementKind::Switch(Switch {
+                scrutinee,
+                branches,
+            }) => StatementKind::Switch(Switch {
+                scrutinee: self.producer(scrutinee),
+                branches: branches
+                    .into_iter()
+                    .map(|branch| self.branch(branch))
+                    .collect(),
+            }),
+            StatementKind::Invoke(Invoke {
+                name,
+                producers,
+                consumers,
+            }) => StatementKind::Invoke(Invoke {
+                name: self.lookup_definition(name, &location),
+                producers: self.producers(producers),
+                consumers: self.consumers(consumers),
+            }),
+            StatementKind::Error => StatementKind::Error,
+        };
+        Statement { location, kind }
+    }
+
+    fn branch(&mut self, branch: Branch<String>) -> Branch<Name> {
+        let Branch { location, kind } = branch;
+        let kind = match kind {
+            BranchKind::LiteralBranch(LiteralBranch { literal, body }) => {
+                BranchKind::LiteralBranch(LiteralBranch {
+                    literal,
+                    body: self.statement(body),
+                })
+            }
+            BranchKind::DefaultBranch(body) => BranchKind::DefaultBranch(self.statement(body)),
+        };
+        Branch { location, kind }
+    }
+
+    fn producers(&mut self, producers: Vec<Producer<String>>) -> Vec<Producer<Name>> {
+        producers
+            .into_iter()
+            .map(|producer| self.producer(producer))
+            .collect()
+    }
+
+    fn producer(&mut self, producer: Producer<String>) -> Producer<Name> {
+        let Producer { location, kind } = producer;
+        let kind = match kind {
+            ProducerKind::Variable(Variable { name }) => ProducerKind::Variable(Variable {
+                name: self.lookup(name, Sort::Variable, &location),
+            }),
+            ProducerKind::Literal(literal) => ProducerKind::Literal(literal),
+            ProducerKind::Do(Do { binder, body }) => {
+                let (_, mut returns, body) =
+                    self.scoped(vec![], vec![binder], |resolver| resolver.statement(*body));
+                ProducerKind::Do(Do {
+                    binder: returns.remove(0),
+                    body: Box::new(body),
+                })
+            }
+            ProducerKind::Construct(Construct {
+                tag,
+                producers,
+                consumers,
+            }) => ProducerKind::Construct(Construct {
+                tag,
+                producers: self.producers(producers),
+                consumers: self.consumers(consumers),
+            }),
+            ProducerKind::Comatch(Comatch { clauses }) => ProducerKind::Comatch(Comatch {
+                clauses: clauses
+                    .into_iter()
+                    .map(|clause| self.coclause(clause))
+                    .collect(),
+            }),
+            ProducerKind::Error => ProducerKind::Error,
+        };
+        Producer { location, kind }
+    }
+
+    fn coclause(&mut self, coclause: Coclause<String>) -> Coclause<Name> {
+        let Coclause {
+            location,
+            copattern:
+                Copattern {
+                    tag,
+                    parameters,
+                    returns,
+                },
+            body,
+        } = coclause;
+        let (parameters, returns, body) =
+            self.scoped(parameters, returns, |resolver| resolver.statement(body));
+        Coclause {
+            location,
+            copattern: Copattern {
+                tag,
+                parameters,
+                returns,
+            },
+            body,
+        }
+    }
+
+    fn consumers(&mut self, consumers: Vec<Consumer<String>>) -> Vec<Consumer<Name>> {
+        consumers
+            .into_iter()
+            .map(|consumer| self.consumer(consumer))
+            .collect()
+    }
+
+    fn consumer(&mut self, consumer: Consumer<String>) -> Consumer<Name> {
+        let Consumer { location, kind } = consumer;
+        let kind = match kind {
+            ConsumerKind::Finish => ConsumerKind::Finish,
+            ConsumerKind::Variable(Variable { name }) => ConsumerKind::Variable(Variable {
+                name: self.lookup(name, Sort::Covariable, &location),
+            }),
+            ConsumerKind::Then(Then { binder, body }) => {
+                let (mut parameters, _, body) =
+                    self.scoped(vec![binder], vec![], |resolver| resolver.statement(*body));
+                ConsumerKind::Then(Then {
+                    binder: parameters.remove(0),
+                    body: Box::new(body),
+                })
+            }
+            ConsumerKind::Destruct(Destruct {
+                tag,
+                producers,
+                consumers,
+            }) => ConsumerKind::Destruct(Destruct {
+                tag,
+                producers: self.producers(producers),
+                consumers: self.consumers(consumers),
+            }),
+            ConsumerKind::Match(Match { clauses }) => ConsumerKind::Match(Match {
+                clauses: clauses
+                    .into_iter()
+                    .map(|clause| self.clause(clause))
+                    .collect(),
+            }),
+            ConsumerKind::Error => ConsumerKind::Error,
+        };
+        Consumer { location, kind }
+    }
+
+    fn clause(&mut self, clause: Clause<String>) -> Clause<Name> {
+        let Clause {
+            location,
+            pattern:
+                Pattern {
+                    tag,
+                    parameters,
+                    returns,
+                },
+            body,
+        } = clause;
+        let (parameters, returns, body) =
+            self.scoped(parameters, returns, |resolver| resolver.statement(body));
+        Clause {
+            location,
+            pattern: Pattern {
+                tag,
+                parameters,
+                returns,
+            },
+            body,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{lexer, parser};
+
+    /// Parses a program that has no syntax errors.
+    fn parse(source: &str) -> Program<String> {
+        let (program, errors) =
+            parser::Parser::new(lexer::Lexer::new("test.coco".to_string(), source)).parse();
+        assert!(errors.is_empty(), "{:?}", errors);
+        program
+    }
+
+    /// Parses and resolves a program that has no errors.
+    pub(crate) fn program(source: &str) -> Program<Name> {
+        program_with(source, &mut Supply::new())
+    }
+
+    /// Parses and resolves a program that has no errors, taking names from `names`.
+    pub(crate) fn program_with(source: &str, names: &mut Supply) -> Program<Name> {
+        let (program, errors) = Resolver::new(names).resolve(parse(source));
+        assert!(errors.is_empty(), "{:?}", errors);
+        program
+    }
+
+    fn resolve(source: &str) -> Vec<Error> {
+        Resolver::new(&mut Supply::new()).resolve(parse(source)).1
+    }
+
+    /// Returns the text a location spans in an ASCII source.
+    fn text<'a>(source: &'a str, location: &location::Location) -> &'a str {
+        &source[location.start..location.end]
+    }
+
+    #[test]
+    fn sort_mismatch_points_at_the_binder() {
+        let source = "def f(x; k) = x | then y k | y";
+        let errors = resolve(source);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        for error in &errors {
+            let Error::SortMismatch {
+                name,
+                location,
+                binder,
+                ..
+            } = error
+            else {
+                panic!("expected a sort mismatch, got {:?}", error);
+            };
+            assert_eq!(text(source, location), name);
+            assert_eq!(text(source, binder), name);
+            assert!(binder.start < location.start);
+        }
+    }
+
+    #[test]
+    fn unbound_variable_suggests_a_binder() {
+        let source = "def f(; k) = Cons(; then value valeu | k) | k";
+        let [Error::UnboundVariable {
+            name,
+            similar: Some((similar, binder)),
+            ..
+        }] = &resolve(source)[..]
+        else {
+            panic!("expected an unbound variable with a suggestion");
+        };
+        assert_eq!((name.as_str(), similar.as_str()), ("valeu", "value"));
+        assert_eq!((binder.start, text(source, binder)), (25, "value"));
+    }
+
+    #[test]
+    fn duplicate_binders() {
+        let source = "def f(x, y, x; k) = comatch { Ap(z; j, j) -> x | j } | k";
+        let errors = resolve(source);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        let duplicates: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                Error::Duplicate {
+                    what,
+                    name,
+                    location,
+                    previous,
+                } => (*what, name.as_str(), previous.start, location.start),
+                error => panic!("expected a duplicate, got {:?}", error),
+            })
+            .collect();
+        assert_eq!(
+            duplicates,
+            [("variable", "x", 6, 12), ("covariable", "j", 36, 39)]
+        );
+    }
+
+    #[test]
+    fn variables_and_covariables_may_share_a_name() {
+        assert!(resolve("def f(x; x) = x | x").is_empty());
+    }
+
+    #[test]
+    fn duplicate_declarations() {
+        let source = "data A { C(;) }\ndata A { D(;) }\ndata B { C(;) }\ncodata E { C(;) }";
+        let errors = resolve(source);
+        let duplicates: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                Error::Duplicate { what, name, .. } => (*what, name.as_str()),
+                error => panic!("expected a duplicate, got {:?}", error),
+            })
+            .collect();
+        assert_eq!(duplicates, [("type", "A"), ("constructor", "C")]);
+    }
+}
@@ -0,0 +1,19 @@
+use crate::name;
+use crate::prim;
+
+/// State shared by the passes run over one program.
+#[derive(Debug, Default)]
+pub struct Session {
+    pub names: name::Supply,
+    /// Primitives available to `prim[...]`; embedders may register their own.
+    pub primitives: prim::Registry,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            names: name::Supply::new(),
+            primitives: prim::Registry::default(),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+/// Returns the candidate most similar to `name`, if any is similar enough
+/// to be worth suggesting. Ties are broken in favour of the last candidate,
+/// so candidates should be given from the farthest to the nearest.
+pub fn closest<'a, T>(name: &str, candidates: impl IntoIterator<Item = (&'a str, T)>) -> Option<T> {
+    let threshold = name.chars().count() / 3 + 1;
+    let mut best = None;
+    for (candidate, value) in candidates {
+        let distance = edit_distance(name, candidate);
+        if distance > 0
+            && distance <= threshold
+            && best.as_ref().map_or(true, |(d, _)| distance <= *d)
+        {
+            best = Some((distance, value));
+        }
+    }
+    best.map(|(_, value)| value)
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -1,6 +1,6 @@
 use crate::{core_ir, location};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub location: location::Location,
     pub kind: TokenKind,
@@ -30,9 +30,27 @@ impl Token {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TokenKind {
     Identifier(String),
     Literal(core_ir::Literal),
     Punctuation(String),
+    /// Characters that failed to lex; the lexical error is reported on its own.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub location: location::Location,
+    pub kind: CommentKind,
+    /// Whether a line break separates the comment from what comes before it.
+    pub newline_before: bool,
+    /// The comment as written, including its delimiters.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,
+    Block,
 }
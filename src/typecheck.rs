@@ -0,0 +1,989 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::core_ir::*;
+use crate::location;
+use crate::name::Name;
+use crate::prim;
+use crate::visit::{self, Visitor};
+use ariadne::Label;
+use ariadne::Report;
+use ariadne::ReportKind;
+use thiserror::Error;
+
+/// Types of literals, which need no declaration.
+pub const BUILTIN_TYPES: [&str; 4] = ["Int", "Float", "Bool", "String"];
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("expected `{expected}`, found `{actual}`")]
+    Mismatch {
+        expected: Type,
+        actual: Type,
+        location: location::Location,
+    },
+    #[error("`{variable}` would be the infinite type `{ty}`")]
+    InfiniteType {
+        variable: Type,
+        ty: Type,
+        location: location::Location,
+    },
+    #[error("unknown type `{name}`")]
+    UnknownType {
+        name: String,
+        location: location::Location,
+    },
+    #[error("`{name}` takes {expected} type arguments, but {actual} are given")]
+    TypeArguments {
+        name: String,
+        expected: usize,
+        actual: usize,
+        location: location::Location,
+    },
+    #[error("`{name}` needs a type annotation")]
+    MissingAnnotation {
+        name: String,
+        location: location::Location,
+    },
+    #[error("cannot switch on `{ty}`, which has no literals")]
+    NotLiteral {
+        ty: Type,
+        location: location::Location,
+    },
+}
+
+/// Convert Error to a ariadne::Report.
+impl From<Error> for ariadne::Report<'_, location::Location> {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        let (location, label) = match error {
+            Error::Mismatch {
+                actual, location, ..
+            } => (location, format!("this has type `{}`", actual)),
+            Error::InfiniteType { location, .. } => (location, "inferred here".to_string()),
+            Error::UnknownType { location, .. } => (location, "used here".to_string()),
+            Error::TypeArguments { location, .. } => (location, "used here".to_string()),
+            Error::MissingAnnotation { location, .. } => (location, "declared here".to_string()),
+            Error::NotLiteral { location, .. } => (location, "scrutinee".to_string()),
+        };
+        Report::build(ReportKind::Error, location.clone())
+            .with_message(message)
+            .with_label(Label::new(location).with_message(label))
+            .finish()
+    }
+}
+
+/// The results of checking a program.
+pub struct Typing {
+    /// The type of every definition, in program order.
+    pub schemes: Vec<(Name, Scheme)>,
+    /// The name of the type of each `match` and `comatch` whose type is known, by its location.
+    pub clauses: HashMap<location::Location, String>,
+}
+
+/// The inferred type of a definition, generalised over its type variables.
+#[derive(Debug, Clone)]
+pub struct Scheme {
+    pub variables: Vec<String>,
+    pub parameters: Vec<Type>,
+    pub returns: Vec<Type>,
+}
+
+impl std::fmt::Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if !self.variables.is_empty() {
+            write!(f, "forall {}. ", self.variables.join(" "))?;
+        }
+        let join = |types: &[Type]| {
+            types
+                .iter()
+                .map(|ty| ty.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(f, "({};", join(&self.parameters))?;
+        if !self.returns.is_empty() {
+            write!(f, " {}", join(&self.returns))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Types during inference, where unknowns are numbered unification variables.
+#[derive(Debug, Clone)]
+enum Ty {
+    Unknown(usize),
+    /// A type variable of an annotation while its definition is checked,
+    /// which stands for any type and so equals only itself.
+    Rigid(usize, String),
+    Named(String, Vec<Ty>),
+}
+
+fn named(name: &str) -> Ty {
+    Ty::Named(name.to_string(), Vec::new())
+}
+
+fn literal_type(literal: &Literal) -> Ty {
+    named(match literal {
+        Literal::Int(_) => "Int",
+        Literal::Float(_) => "Float",
+        Literal::Bool(_) => "Bool",
+        Literal::String(_) => "String",
+    })
+}
+
+/// Infers the types of a resolved program in the style of Hindley and Milner.
+///
+/// Definitions are inferred in dependency order, one strongly connected group of
+/// mutually recursive definitions at a time, and generalised before their uses
+/// in later groups, so each use may instantiate them at different types.
+/// Annotations are optional and constrain the inferred types;
+/// a type variable in the annotations of a definition stands for the same type throughout it.
+pub struct Checker<'a> {
+    program: &'a Program<Name>,
+    primitives: &'a prim::Registry,
+    definitions: HashMap<&'a Name, &'a Definition<Name>>,
+    /// Generalised types of the definitions inferred so far.
+    schemes: HashMap<Name, Scheme>,
+    /// Types of the definitions in the group being inferred.
+    group: HashMap<Name, (Vec<Ty>, Vec<Ty>)>,
+    /// Solutions of the unification variables.
+    solutions: Vec<Option<Ty>>,
+    /// Number of rigid type variables made so far.
+    rigid: usize,
+    /// Types of the producers bound to variables.
+    variables: HashMap<Name, Ty>,
+    /// Types of the producers consumed by covariables.
+    covariables: HashMap<Name, Ty>,
+    /// Scrutinees of `switch`, checked once their types are known.
+    scrutinees: Vec<(Ty, location::Location)>,
+    /// Types of the `match`es and `comatch`es, named once they are known.
+    clauses: Vec<(Ty, location::Location)>,
+    /// See `Typing::clauses`.
+    clause_types: HashMap<location::Location, String>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(program: &'a Program<Name>, primitives: &'a prim::Registry) -> Self {
+        Self {
+            program,
+            primitives,
+            definitions: program
+                .definitions
+                .iter()
+                .map(|definition| (&definition.name, definition))
+                .collect(),
+            schemes: HashMap::new(),
+            group: HashMap::new(),
+            solutions: Vec::new(),
+            rigid: 0,
+            variables: HashMap::new(),
+            covariables: HashMap::new(),
+            scrutinees: Vec::new(),
+            clauses: Vec::new(),
+            clause_types: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Returns the types found and the errors.
+    pub fn check(mut self) -> (Typing, Vec<Error>) {
+        let program = self.program;
+        for declaration in &program.declarations {
+            for signature in &declaration.signatures {
+                for binder in signature.parameters.iter().chain(&signature.returns) {
+                    match &binder.ty {
+                        Some(ty) => {
+                            self.validate(ty, Some(&declaration.parameters), &signature.location)
+                        }
+                        None => self.errors.push(Error::MissingAnnotation {
+                            name: binder.name.clone(),
+                            location: signature.location.clone(),
+                        }),
+                    }
+                }
+            }
+        }
+        for group in self.groups() {
+            self.infer_group(&group);
+        }
+        let schemes = program
+            .definitions
+            .iter()
+            .filter_map(|definition| {
+                let scheme = self.schemes.get(&definition.name)?;
+                Some((definition.name.clone(), scheme.clone()))
+            })
+            .collect();
+        let typing = Typing {
+            schemes,
+            clauses: self.clause_types,
+        };
+        (typing, self.errors)
+    }
+
+    /// Reports unknown type names, wrong numbers of type arguments,
+    /// and type variables that are not `parameters` of the enclosing declaration.
+    fn validate(
+        &mut self,
+        ty: &Type,
+        parameters: Option<&[String]>,
+        location: &location::Location,
+    ) {
+        match ty {
+            Type::Named(name, arguments) => {
+                let expected = if BUILTIN_TYPES.contains(&name.as_str()) {
+                    Some(0)
+                } else {
+                    self.program
+                        .declarations
+                        .iter()
+                        .find(|declaration| declaration.name == *name)
+                        .map(|declaration| declaration.parameters.len())
+                };
+                match expected {
+                    None => self.errors.push(Error::UnknownType {
+                        name: name.clone(),
+                        location: location.clone(),
+                    }),
+                    Some(expected) if expected != arguments.len() => {
+                        self.errors.push(Error::TypeArguments {
+                            name: name.clone(),
+                            expected,
+                            actual: arguments.len(),
+                            location: location.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                }
+                for argument in arguments {
+                    self.validate(argument, parameters, location);
+                }
+            }
+            Type::Variable(name) => {
+                if parameters.is_some_and(|parameters| !parameters.contains(name)) {
+                    self.errors.push(Error::UnknownType {
+                        name: name.clone(),
+                        location: location.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Splits the definitions into groups of mutually recursive ones,
+    /// each after the groups it invokes (Tarjan's algorithm).
+    fn groups(&self) -> Vec<Vec<&'a Definition<Name>>> {
+        struct Search<'a, 'b> {
+            checker: &'b Checker<'a>,
+            index: HashMap<&'a Name, usize>,
+            lowlink: HashMap<&'a Name, usize>,
+            stack: Vec<&'a Definition<Name>>,
+            groups: Vec<Vec<&'a Definition<Name>>>,
+        }
+
+        impl<'a> Search<'a, '_> {
+            fn visit(&mut self, definition: &'a Definition<Name>) {
+                let index = self.index.len();
+                self.index.insert(&definition.name, index);
+                self.lowlink.insert(&definition.name, index);
+                self.stack.push(definition);
+
+                let mut callees = Invokes(Vec::new());
+                callees.statement(&definition.body);
+                for callee in callees.0 {
+                    let Some(callee) = self.checker.definitions.get(callee).copied() else {
+                        continue;
+                    };
+                    if !self.index.contains_key(&callee.name) {
+                        self.visit(callee);
+                        let low = self.lowlink[&definition.name].min(self.lowlink[&callee.name]);
+                        self.lowlink.insert(&definition.name, low);
+                    } else if self.stack.iter().any(|d| d.name == callee.name) {
+                        let low = self.lowlink[&definition.name].min(self.index[&callee.name]);
+                        self.lowlink.insert(&definition.name, low);
+                    }
+                }
+
+                if self.lowlink[&definition.name] == index {
+                    let position = self
+                        .stack
+                        .iter()
+                        .position(|d| d.name == definition.name)
+                        .unwrap();
+                    let group = self.stack.split_off(position);
+                    self.groups.push(group);
+                }
+            }
+        }
+
+        let mut search = Search {
+            checker: self,
+            index: HashMap::new(),
+            lowlink: HashMap::new(),
+            stack: Vec::new(),
+            groups: Vec::new(),
+        };
+        for definition in &self.program.definitions {
+            if !search.index.contains_key(&definition.name) {
+                search.visit(definition);
+            }
+        }
+        search.groups
+    }
+
+    fn infer_group(&mut self, group: &[&'a Definition<Name>]) {
+        self.group.clear();
+        for definition in group {
+            // Type variables in annotations are rigid and shared within a definition.
+            let mut variables = HashMap::new();
+            for binder in definition.parameters.iter().chain(&definition.returns) {
+                if let Some(ty) = &binder.ty {
+                    self.skolemise(ty, &mut variables);
+                }
+            }
+            let mut binders = |checker: &mut Self, binders: &[Binder<Name>]| {
+                binders
+                    .iter()
+                    .map(|binder| match &binder.ty {
+                        Some(ty) => {
+                            checker.validate(ty, None, &definition.location);
+                            checker.instantiate(ty, &mut variables)
+                        }
+                        None => checker.fresh(),
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let parameters = binders(self, &definition.parameters);
+            let returns = binders(self, &definition.returns);
+            for (binder, ty) in definition.parameters.iter().zip(&parameters) {
+                self.variables.insert(binder.name.clone(), ty.clone());
+            }
+            for (binder, ty) in definition.returns.iter().zip(&returns) {
+                self.covariables.insert(binder.name.clone(), ty.clone());
+            }
+            self.group
+                .insert(definition.name.clone(), (parameters, returns));
+        }
+
+        for definition in group {
+            self.statement(&definition.body);
+        }
+
+        for (ty, location) in std::mem::take(&mut self.scrutinees) {
+            let literal = match self.resolve(&ty) {
+                Ty::Unknown(_) => true,
+                Ty::Rigid(..) => false,
+                Ty::Named(name, _) => BUILTIN_TYPES.contains(&name.as_str()),
+            };
+            if !literal {
+                let ty = self.export(&ty, &mut self.names([&ty]));
+                self.errors.push(Error::NotLiteral { ty, location });
+            }
+        }
+
+        for (ty, location) in std::mem::take(&mut self.clauses) {
+            if let Ty::Named(name, _) = self.resolve(&ty) {
+                self.clause_types.insert(location, name);
+            }
+        }
+
+        for definition in group {
+            let (parameters, returns) = self.group[&definition.name].clone();
+            let mut names = self.names(parameters.iter().chain(&returns));
+            let parameters = parameters
+                .iter()
+                .map(|ty| self.export(ty, &mut names))
+                .collect();
+            let returns = returns
+                .iter()
+                .map(|ty| self.export(ty, &mut names))
+                .collect();
+            let mut variables: Vec<_> = names.unknowns.into_values().chain(names.rigid).collect();
+            variables.sort_by_key(|name: &String| (name.len(), name.clone()));
+            self.schemes.insert(
+                definition.name.clone(),
+                Scheme {
+                    variables,
+                    parameters,
+                    returns,
+                },
+            );
+        }
+    }
+
+    fn fresh(&mut self) -> Ty {
+        self.solutions.push(None);
+        Ty::Unknown(self.solutions.len() - 1)
+    }
+
+    /// Converts a type to a `Ty`, replacing each type variable with the type in `variables`,
+    /// or with a fresh unknown that is remembered there.
+    fn instantiate(&mut self, ty: &Type, variables: &mut HashMap<String, Ty>) -> Ty {
+        match ty {
+            Type::Named(name, arguments) => Ty::Named(
+                name.clone(),
+                arguments
+                    .iter()
+                    .map(|argument| self.instantiate(argument, variables))
+                    .collect(),
+            ),
+            Type::Variable(name) => {
+                if let Some(ty) = variables.get(name) {
+                    return ty.clone();
+                }
+                let ty = self.fresh();
+                variables.insert(name.clone(), ty.clone());
+                ty
+            }
+        }
+    }
+
+    /// Replaces each type variable in `ty` that is not yet in `variables` with a new rigid variable.
+    fn skolemise(&mut self, ty: &Type, variables: &mut HashMap<String, Ty>) {
+        match ty {
+            Type::Named(_, arguments) => {
+                for argument in arguments {
+                    self.skolemise(argument, variables);
+                }
+            }
+            Type::Variable(name) => {
+                if !variables.contains_key(name) {
+                    self.rigid += 1;
+                    variables.insert(name.clone(), Ty::Rigid(self.rigid, name.clone()));
+                }
+            }
+        }
+    }
+
+    /// Names for exporting `types` together, which must not capture their rigid variables.
+    fn names<'t>(&self, types: impl IntoIterator<Item = &'t Ty>) -> Names {
+        fn collect(checker: &Checker, ty: &Ty, rigid: &mut HashSet<String>) {
+            match checker.resolve(ty) {
+                Ty::Unknown(_) => {}
+                Ty::Rigid(_, name) => {
+                    rigid.insert(name);
+                }
+                Ty::Named(_, arguments) => {
+                    for argument in &arguments {
+                        collect(checker, argument, rigid);
+                    }
+                }
+            }
+        }
+        let mut names = Names::default();
+        for ty in types {
+            collect(self, ty, &mut names.rigid);
+        }
+        names
+    }
+
+    /// Converts a `Ty` back to a type, naming the unknowns left `a`, `b`, ... in order of appearance,
+    /// skipping the names of rigid variables.
+    fn export(&self, ty: &Ty, names: &mut Names) -> Type {
+        match self.resolve(ty) {
+            Ty::Unknown(unknown) => {
+                if let Some(name) = names.unknowns.get(&unknown) {
+                    return Type::Variable(name.clone());
+                }
+                let name = (names.unknowns.len()..)
+                    .map(variable_name)
+                    .find(|name| !names.rigid.contains(name))
+                    .expect("variable names are unbounded");
+                names.unknowns.insert(unknown, name.clone());
+                Type::Variable(name)
+            }
+            Ty::Rigid(_, name) => Type::Variable(name),
+            Ty::Named(name, arguments) => Type::Named(
+                name,
+                arguments
+                    .iter()
+                    .map(|argument| self.export(argument, names))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Follows the solutions of unknowns at the top of `ty`.
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Unknown(unknown) => match &self.solutions[*unknown] {
+                Some(solution) => self.resolve(solution),
+                None => ty.clone(),
+            },
+            Ty::Rigid(..) | Ty::Named(..) => ty.clone(),
+        }
+    }
+
+    fn occurs(&self, unknown: usize, ty: &Ty) -> bool {
+        match self.resolve(ty) {
+            Ty::Unknown(other) => unknown == other,
+            Ty::Rigid(..) => false,
+            Ty::Named(_, arguments) => arguments.iter().any(|ty| self.occurs(unknown, ty)),
+        }
+    }
+
+    /// Unifies the type a consumer or binder `expected` with the type of a producer,
+    /// reporting a mismatch at `location`.
+    fn unify(&mut self, expected: &Ty, actual: &Ty, location: &location::Location) {
+        if !self.unify_types(expected, actual, location) {
+            let mut names = self.names([expected, actual]);
+            let expected = self.export(expected, &mut names);
+            let actual = self.export(actual, &mut names);
+            self.errors.push(Error::Mismatch {
+                expected,
+                actual,
+                location: location.clone(),
+            });
+        }
+    }
+
+    /// Returns false on a mismatch. Infinite types are reported here.
+    fn unify_types(&mut self, left: &Ty, right: &Ty, location: &location::Location) -> bool {
+        match (self.resolve(left), self.resolve(right)) {
+            (Ty::Unknown(left), Ty::Unknown(right)) if left == right => true,
+            (Ty::Unknown(unknown), ty) | (ty, Ty::Unknown(unknown)) => {
+                if self.occurs(unknown, &ty) {
+                    let mut names = self.names([&ty]);
+                    let variable = self.export(&Ty::Unknown(unknown), &mut names);
+                    let ty = self.export(&ty, &mut names);
+                    self.errors.push(Error::InfiniteType {
+                        variable,
+                        ty,
+                        location: location.clone(),
+                    });
+                } else {
+                    self.solutions[unknown] = Some(ty);
+                }
+                true
+            }
+            (Ty::Rigid(left, _), Ty::Rigid(right, _)) => left == right,
+            (Ty::Named(left, left_arguments), Ty::Named(right, right_arguments)) => {
+                left == right
+                    && left_arguments.len() == right_arguments.len()
+                    && left_arguments
+                        .iter()
+                        .zip(&right_arguments)
+                        .all(|(left, right)| self.unify_types(left, right, location))
+            }
+            (Ty::Rigid(..), Ty::Named(..)) | (Ty::Named(..), Ty::Rigid(..)) => false,
+        }
+    }
+
+    /// Instantiates the declaration of `tag`, returning the declared type
+    /// and the types of the constructor's or destructor's parameters and returns.
+    fn signature(&mut self, kind: DeclarationKind, tag: &str) -> Option<(Ty, Vec<Ty>, Vec<Ty>)> {
+        // Unknown tags are reported by the arity check.
+        let (declaration, signature) = self.program.signature(kind, tag)?;
+        let mut variables = HashMap::new();
+        let arguments = declaration
+            .parameters
+            .iter()
+            .map(|parameter| self.instantiate(&Type::Variable(parameter.clone()), &mut variables))
+            .collect();
+        let mut binders = |checker: &mut Self, binders: &[Binder<String>]| {
+            binders
+                .iter()
+                .map(|binder| match &binder.ty {
+                    Some(ty) => checker.instantiate(ty, &mut variables),
+                    // Missing annotations are reported at the declaration.
+                    None => checker.fresh(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let parameters = binders(self, &signature.parameters);
+        let returns = binders(self, &signature.returns);
+        Some((
+            Ty::Named(declaration.name.clone(), arguments),
+            parameters,
+            returns,
+        ))
+    }
+
+    fn statement(&mut self, statement: &Statement<Name>) {
+        match &statement.kind {
+            StatementKind::Cut(Cut { producer, consumer }) => {
+                let actual = self.producer(producer);
+                let expected = self.consumer(consumer);
+                self.unify(&expected, &actual, &statement.location);
+            }
+            StatementKind::Prim(Prim {
+                name,
+                producers,
+                consumers,
+            }) => {
+                // Unknown primitives are reported by `prim::Checker`.
+                let (producer_types, consumer_types) = match self.primitives.get(name) {
+                    Some(primitive) => {
+                        let mut variables = HashMap::new();
+                        let mut instantiate = |checker: &mut Self, types: &[Type]| {
+                            types
+                                .iter()
+                                .map(|ty| checker.instantiate(ty, &mut variables))
+                                .collect::<Vec<_>>()
+                        };
+                        (
+                            instantiate(self, &primitive.parameters),
+                            instantiate(self, &primitive.returns),
+                        )
+                    }
+                    None => (Vec::new(), Vec::new()),
+                };
+                self.arguments(producers, consumers, &producer_types, &consumer_types);
+            }
+            StatementKind::Switch(Switch {
+                scrutinee,
+                branches,
+            }) => {
+                let ty = self.producer(scrutinee);
+                self.scrutinees
+                    .push((ty.clone(), scrutinee.location.clone()));
+                for branch in branches {
+                    match &branch.kind {
+                        BranchKind::LiteralBranch(LiteralBranch { literal, body }) => {
+                            self.unify(&ty, &literal_type(literal), &branch.location);
+                            self.statement(body);
+                        }
+                        BranchKind::DefaultBranch(body) => self.statement(body),
+                    }
+                }
+            }
+            StatementKind::Invoke(Invoke {
+                name,
+                producers,
+                consumers,
+            }) => {
+                let (producer_types, consumer_types) = if let Some(types) = self.group.get(name) {
+                    types.clone()
+                } else if let Some(scheme) = self.schemes.get(name).cloned() {
+                    let mut variables = HashMap::new();
+                    let mut instantiate = |checker: &mut Self, types: &[Type]| {
+                        types
+                            .iter()
+                            .map(|ty| checker.instantiate(ty, &mut variables))
+                            .collect::<Vec<_>>()
+                    };
+                    (
+                        instantiate(self, &scheme.parameters),
+                        instantiate(self, &scheme.returns),
+                    )
+                } else {
+                    // Unknown definitions are reported by name resolution.
+                    (Vec::new(), Vec::new())
+                };
+                self.arguments(producers, consumers, &producer_types, &consumer_types);
+            }
+            StatementKind::Error => {}
+        }
+    }
+
+    /// Infers the arguments and unifies them with the expected types, if any.
+    fn arguments(
+        &mut self,
+        producers: &[Producer<Name>],
+        consumers: &[Consumer<Name>],
+        producer_types: &[Ty],
+        consumer_types: &[Ty],
+    ) {
+        for (i, producer) in producers.iter().enumerate() {
+            let actual = self.producer(producer);
+            if let Some(expected) = producer_types.get(i) {
+                self.unify(expected, &actual, &producer.location);
+            }
+        }
+        for (i, consumer) in consumers.iter().enumerate() {
+            let actual = self.consumer(consumer);
+            if let Some(expected) = consumer_types.get(i) {
+                self.unify(&actual, expected, &consumer.location);
+            }
+        }
+    }
+
+    /// Binds the variables of a pattern or copattern to the given types.
+    fn bind(
+        &mut self,
+        parameters: &[Binder<Name>],
+        returns: &[Binder<Name>],
+        types: (Vec<Ty>, Vec<Ty>),
+    ) {
+        for (binder, ty) in parameters.iter().zip(types.0) {
+            self.variables.insert(binder.name.clone(), ty);
+        }
+        for (binder, ty) in returns.iter().zip(types.1) {
+            self.covariables.insert(binder.name.clone(), ty);
+        }
+    }
+
+    /// Infers the type of a producer.
+    fn producer(&mut self, producer: &Producer<Name>) -> Ty {
+        match &producer.kind {
+            ProducerKind::Variable(Variable { name }) => match self.variables.get(name) {
+                Some(ty) => ty.clone(),
+                None => self.fresh(),
+            },
+            ProducerKind::Literal(literal) => literal_type(literal),
+            ProducerKind::Do(Do { binder, body }) => {
+                let ty = self.fresh();
+                self.covariables.insert(binder.name.clone(), ty.clone());
+                self.statement(body);
+                ty
+            }
+            ProducerKind::Construct(Construct {
+                tag,
+                producers,
+                consumers,
+            }) => match self.signature(DeclarationKind::Data, tag) {
+                Some((ty, producer_types, consumer_types)) => {
+                    self.arguments(producers, consumers, &producer_types, &consumer_types);
+                    ty
+                }
+                None => {
+                    self.arguments(producers, consumers, &[], &[]);
+                    self.fresh()
+                }
+            },
+            ProducerKind::Comatch(Comatch { clauses }) => {
+                let ty = self.fresh();
+                for clause in clauses {
+                    let Copattern {
+                        tag,
+                        parameters,
+                        returns,
+                    } = &clause.copattern;
+                    if let Some((actual, producer_types, consumer_types)) =
+                        self.signature(DeclarationKind::Codata, tag)
+                    {
+                        self.unify(&ty, &actual, &clause.location);
+                        self.bind(parameters, returns, (producer_types, consumer_types));
+                    }
+                    self.statement(&clause.body);
+                }
+                self.clauses.push((ty.clone(), producer.location.clone()));
+                ty
+            }
+            ProducerKind::Error => self.fresh(),
+        }
+    }
+
+    /// Infers the type of the producers a consumer consumes.
+    fn consumer(&mut self, consumer: &Consumer<Name>) -> Ty {
+        match &consumer.kind {
+            // The end of the program accepts a producer of any type.
+            ConsumerKind::Finish => self.fresh(),
+            ConsumerKind::Variable(Variable { name }) => match self.covariables.get(name) {
+                Some(ty) => ty.clone(),
+                None => self.fresh(),
+            },
+            ConsumerKind::Then(Then { binder, body }) => {
+                let ty = self.fresh();
+                self.variables.insert(binder.name.clone(), ty.clone());
+                self.statement(body);
+                ty
+            }
+            ConsumerKind::Destruct(Destruct {
+                tag,
+                producers,
+                consumers,
+            }) => match self.signature(DeclarationKind::Codata, tag) {
+                Some((ty, producer_types, consumer_types)) => {
+                    self.arguments(producers, consumers, &producer_types, &consumer_types);
+                    ty
+                }
+                None => {
+                    self.arguments(producers, consumers, &[], &[]);
+                    self.fresh()
+                }
+            },
+            ConsumerKind::Match(Match { clauses }) => {
+                let ty = self.fresh();
+                for clause in clauses {
+                    let Pattern {
+                        tag,
+                        parameters,
+                        returns,
+                    } = &clause.pattern;
+                    if let Some((actual, producer_types, consumer_types)) =
+                        self.signature(DeclarationKind::Data, tag)
+                    {
+                        self.unify(&ty, &actual, &clause.location);
+                        self.bind(parameters, returns, (producer_types, consumer_types));
+                    }
+                    self.statement(&clause.body);
+                }
+                self.clauses.push((ty.clone(), consumer.location.clone()));
+                ty
+            }
+            ConsumerKind::Error => self.fresh(),
+        }
+    }
+}
+
+/// Collects the names of the definitions invoked in a statement.
+struct Invokes<'a>(Vec<&'a Name>);
+
+impl<'a> Visitor<'a, Name> for Invokes<'a> {
+    fn statement(&mut self, statement: &'a Statement<Name>) {
+        if let StatementKind::Invoke(Invoke { name, .. }) = &statement.kind {
+            self.0.push(name);
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+/// Names given to the unknowns of types exported together.
+#[derive(Default)]
+struct Names {
+    unknowns: HashMap<usize, String>,
+    /// Names of the rigid variables in the types, which unknowns must not take.
+    rigid: HashSet<String>,
+}
+
+/// Names type variables `a` to `z`, then `a1`, `b1`, ...
+fn variable_name(index: usize) -> String {
+    let letter = (b'a' + (index % 26) as u8) as char;
+    match index / 26 {
+        0 => letter.to_string(),
+        round => format!("{}{}", letter, round),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::tests::program;
+
+    const LIST: &str = "data List[a] { Nil(;), Cons(x: a, xs: List[a];) }\n";
+
+    fn check(source: &str) -> (Vec<(String, String)>, Vec<Error>) {
+        let program = program(source);
+        let primitives = prim::Registry::default();
+        let (typing, errors) = Checker::new(&program, &primitives).check();
+        let schemes = typing
+            .schemes
+            .into_iter()
+            .map(|(name, scheme)| (name.text, scheme.to_string()))
+            .collect();
+        (schemes, errors)
+    }
+
+    /// Returns the type of every definition, expecting no errors.
+    fn schemes(source: &str) -> Vec<(String, String)> {
+        let (schemes, errors) = check(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        schemes
+    }
+
+    #[test]
+    fn infers_and_generalises() {
+        let source = format!(
+            "{}def id(x; k) = x | k\n\
+             def singleton(x; k) = Cons(x, Nil(;);) | k\n\
+             def both(; k) = invoke[id](1; then n invoke[id](\"s\"; then s invoke[singleton](n; k)))",
+            LIST
+        );
+        let schemes = schemes(&source);
+        let schemes: Vec<_> = schemes
+            .iter()
+            .map(|(name, scheme)| (name.as_str(), scheme.as_str()))
+            .collect();
+        assert_eq!(
+            schemes,
+            [
+                ("id", "forall a. (a; a)"),
+                ("singleton", "forall a. (a; List[a])"),
+                ("both", "(; List[Int])"),
+            ]
+        );
+    }
+
+    #[test]
+    fn annotations_constrain() {
+        let source = "def f(x: Int; k) = x | k";
+        assert_eq!(
+            schemes(source),
+            [("f".to_string(), "(Int; Int)".to_string())]
+        );
+        let (_, errors) = check("def f(x: Int; k: String) = x | k");
+        let [Error::Mismatch {
+            expected, actual, ..
+        }] = &errors[..]
+        else {
+            panic!("expected a mismatch, got {:?}", errors);
+        };
+        assert_eq!(
+            (expected.to_string(), actual.to_string()),
+            ("String".to_string(), "Int".to_string())
+        );
+    }
+
+    #[test]
+    fn annotation_variables_are_rigid() {
+        for source in [
+            "def bad(x: a; k: b) = x | k",
+            "def bad2(x: a; k: Int) = x | k",
+            "def bad3(x; k: a) = 1 | k",
+        ] {
+            let (_, errors) = check(source);
+            assert!(
+                matches!(&errors[..], [Error::Mismatch { .. }]),
+                "{}: {:?}",
+                source,
+                errors
+            );
+        }
+        let (_, errors) = check("def bad(x: a; k: b) = x | k");
+        let [Error::Mismatch {
+            expected, actual, ..
+        }] = &errors[..]
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            (expected.to_string(), actual.to_string()),
+            ("b".to_string(), "a".to_string())
+        );
+        assert_eq!(
+            schemes("def id(x: a; k: a) = x | k\ndef pair(x: b; k) = x | k"),
+            [
+                ("id".to_string(), "forall a. (a; a)".to_string()),
+                ("pair".to_string(), "forall b. (b; b)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn cut_mismatch() {
+        let source = format!(
+            "{}def f(; k) = 1 | match {{ Nil(;) -> 1 | k, Cons(x, xs;) -> x | k }}",
+            LIST
+        );
+        let (_, errors) = check(&source);
+        assert!(
+            matches!(&errors[..], [Error::Mismatch { expected, .. }] if expected.to_string() == "List[Int]"),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn switch_needs_literals() {
+        let source = format!("{}def f(; k) = switch Nil(;) {{ _ -> 1 | k }}", LIST);
+        let (_, errors) = check(&source);
+        assert!(
+            matches!(&errors[..], [Error::NotLiteral { .. }]),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn declarations_need_annotations() {
+        let (_, errors) = check("data A { C(x;) }");
+        assert!(
+            matches!(&errors[..], [Error::MissingAnnotation { name, .. }] if name == "x"),
+            "{:?}",
+            errors
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use crate::core_ir::*;
+
+/// A read-only pass over the statements, producers and consumers of a program.
+///
+/// Each method visits the children of its node by default. Passes override the
+/// nodes they are interested in and call the matching `walk_*` function to go on
+/// into the children.
+pub trait Visitor<'a, N: 'a> {
+    fn statement(&mut self, statement: &'a Statement<N>) {
+        walk_statement(self, statement);
+    }
+
+    fn producer(&mut self, producer: &'a Producer<N>) {
+        walk_producer(self, producer);
+    }
+
+    fn consumer(&mut self, consumer: &'a Consumer<N>) {
+        walk_consumer(self, consumer);
+    }
+}
+
+/// Visits the children of a statement, in the order they appear in the source.
+pub fn walk_statement<'a, N, V>(visitor: &mut V, statement: &'a Statement<N>)
+where
+    V: Visitor<'a, N> + ?Sized,
+{
+    match &statement.kind {
+        StatementKind::Cut(Cut { producer, consumer }) => {
+            visitor.producer(producer);
+            visitor.consumer(consumer);
+        }
+        StatementKind::Prim(Prim {
+            producers,
+            consumers,
+            ..
+        })
+        | StatementKind::Invoke(Invoke {
+            producers,
+            consumers,
+            ..
+        }) => walk_arguments(visitor, producers, consumers),
+        StatementKind::Switch(Switch {
+            scrutinee,
+            branches,
+        }) => {
+            visitor.producer(scrutinee);
+            for branch in branches {
+                match &branch.kind {
+                    BranchKind::LiteralBranch(LiteralBranch { body, .. })
+                    | BranchKind::DefaultBranch(body) => visitor.statement(body),
+                }
+            }
+        }
+        StatementKind::Error => {}
+    }
+}
+
+/// Visits the children of a producer.
+pub fn walk_producer<'a, N, V>(visitor: &mut V, producer: &'a Producer<N>)
+where
+    V: Visitor<'a, N> + ?Sized,
+{
+    match &producer.kind {
+        ProducerKind::Variable(_) | ProducerKind::Literal(_) | ProducerKind::Error => {}
+        ProducerKind::Do(Do { body, .. }) => visitor.statement(body),
+        ProducerKind::Construct(Construct {
+            producers,
+            consumers,
+            ..
+        }) => walk_arguments(visitor, producers, consumers),
+        ProducerKind::Comatch(Comatch { clauses }) => {
+            for clause in clauses {
+                visitor.statement(&clause.body);
+            }
+        }
+    }
+}
+
+/// Visits the children of a consumer.
+pub fn walk_consumer<'a, N, V>(visitor: &mut V, consumer: &'a Consumer<N>)
+where
+    V: Visitor<'a, N> + ?Sized,
+{
+    match &consumer.kind {
+        ConsumerKind::Finish | ConsumerKind::Variable(_) | ConsumerKind::Error => {}
+        ConsumerKind::Then(Then { body, .. }) => visitor.statement(body),
+        ConsumerKind::Destruct(Destruct {
+            producers,
+            consumers,
+            ..
+        }) => walk_arguments(visitor, producers, consumers),
+        ConsumerKind::Match(Match { clauses }) => {
+            for clause in clauses {
+                visitor.statement(&clause.body);
+            }
+        }
+    }
+}
+
+fn walk_arguments<'a, N, V>(
+    visitor: &mut V,
+    producers: &'a [Producer<N>],
+    consumers: &'a [Consumer<N>],
+) where
+    V: Visitor<'a, N> + ?Sized,
+{
+    for producer in producers {
+        visitor.producer(producer);
+    }
+    for consumer in consumers {
+        visitor.consumer(consumer);
+    }
+}
@@ -0,0 +1,48 @@
+use coco::core_ir::{Literal, Type};
+use coco::machine::{Machine, Strategy};
+use coco::prim::Primitive;
+use coco::session::Session;
+use coco::{focus, lexer, parser, prim, resolve, typecheck};
+
+fn int() -> Type {
+    Type::Named("Int".to_string(), Vec::new())
+}
+
+fn double(arguments: &[Literal]) -> Result<(usize, Literal), String> {
+    match arguments {
+        [Literal::Int(x)] => Ok((0, Literal::Int(x * 2))),
+        _ => Err("expected an integer".to_string()),
+    }
+}
+
+#[test]
+fn runs_a_registered_primitive() {
+    let mut session = Session::new();
+    session.primitives.register(Primitive {
+        name: "double".to_string(),
+        parameters: vec![int()],
+        returns: vec![int()],
+        pure: true,
+        run: double,
+    });
+
+    let source = "def main(; k) = prim[double](21; k)";
+    let lexer = lexer::Lexer::new("embedding.coco".to_string(), source);
+    let (program, errors) = parser::Parser::new(lexer).parse();
+    assert!(errors.is_empty(), "{:?}", errors);
+    let (program, errors) = resolve::Resolver::new(&mut session.names).resolve(program);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let errors = prim::Checker::new(&session.primitives).check(&program);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let (_, errors) = typecheck::Checker::new(&program, &session.primitives).check();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let program = focus::Focuser::new(&mut session.names).focus(program);
+    let mut machine = Machine::new(
+        &program,
+        &session.primitives,
+        &program.definitions[0],
+        Strategy::Value,
+    );
+    assert_eq!(machine.run().unwrap().to_string(), "42");
+}